@@ -10,6 +10,8 @@ pub const PREFER_FAST_COND: bool = true;
 
 // ************ Resources ****************
 pub const MAX_INPUT_LEN: usize = 15000;
+// Largest test case delivered through shared memory
+pub const INPUT_SHM_SIZE: usize = 1 << 20;
 
 // branch.rs
 pub const MAP_SIZE_POW2: usize = 20;
@@ -17,10 +19,20 @@ pub const BRANCHES_SIZE: usize = 1 << MAP_SIZE_POW2;
 
 // executor.rs:
 pub const TMOUT_SKIP: usize = 3;
-pub const TIME_LIMIT: u64 = 1;
+pub const TIME_LIMIT: u64 = 1000; // ms
+// Auto-calibrated time limit: AUTO_TIME_LIMIT_FACTOR times the average
+// execution time of the seeds, rounded up to AUTO_TIME_LIMIT_ROUND ms and
+// at most TIME_LIMIT
+pub const AUTO_TIME_LIMIT_FACTOR: u64 = 5;
+pub const AUTO_TIME_LIMIT_ROUND: u64 = 20; // ms
+// Timeouts are run again with HANG_LIMIT_FACTOR times the time limit, at
+// least TIME_LIMIT, before being saved as hangs
+pub const HANG_LIMIT_FACTOR: u64 = 2;
 pub const MEM_LIMIT: u64 = 200; // MB
-pub const TIME_LIMIT_TRACK: u64 = 12;
+pub const TIME_LIMIT_TRACK: u64 = 12; // s
 pub const MEM_LIMIT_TRACK: u64 = 0;
+// Top frames of the stack identifying a crash in the triage
+pub const CRASH_STACK_FRAMES: usize = 5;
 pub const LONG_FUZZ_TIME: usize = 8;
 pub const MAX_INVARIABLE_NUM: usize = 16;
 pub const MAX_NUM_MINIMAL_OPTIMA_ALL: usize = 28;
@@ -7,6 +7,7 @@ pub static ANGORA_BIN_DIR: &str = "ANGORA_BIN_DIR";
 pub static TRACK_OUTPUT_VAR: &str = "ANGORA_TRACK_OUTPUT";
 pub static COND_STMT_ENV_VAR: &str = "ANGORA_COND_STMT_SHM_ID";
 pub static BRANCHES_SHM_ENV_VAR: &str = "ANGORA_BRANCHES_SHM_ID";
+pub static INPUT_SHM_ENV_VAR: &str = "ANGORA_INPUT_SHM_ID";
 pub static LD_LIBRARY_PATH_VAR: &str = "LD_LIBRARY_PATH";
 pub static ASAN_OPTIONS_VAR: &str = "ASAN_OPTIONS";
 pub static MSAN_OPTIONS_VAR: &str = "MSAN_OPTIONS";
@@ -16,21 +17,40 @@ pub const MSAN_ERROR_CODE: i32 = 86;
 pub static MSAN_OPTIONS_CONTENT: &str =
     "exit_code=86:symbolize=0:abort_on_error=1:allocator_may_return_null=1:msan_track_origins=0";
 pub static DFSAN_OPTIONS_VAR: &str = "DFSAN_OPTIONS";
+// Crash triage, with symbolized stacks
+pub static UBSAN_OPTIONS_VAR: &str = "UBSAN_OPTIONS";
+pub static PATH_VAR: &str = "PATH";
+pub static ASAN_OPTIONS_TRIAGE: &str =
+    "abort_on_error=1:detect_leaks=0:symbolize=1:allocator_may_return_null=1";
+pub static MSAN_OPTIONS_TRIAGE: &str =
+    "exit_code=86:symbolize=1:abort_on_error=1:allocator_may_return_null=1:msan_track_origins=0";
+pub static UBSAN_OPTIONS_TRIAGE: &str = "print_stacktrace=1:symbolize=1";
 
 // depot.rs
 pub static CRASHES_DIR: &str = "crashes";
 pub static HANGS_DIR: &str = "hangs";
 pub static INPUTS_DIR: &str = "queue";
+pub static CRASH_INDEX_FILE: &str = "index.json";
+// Stack of a crash, next to the input, read by `parser annotate`
+pub static CRASH_STACK_EXT: &str = "stack";
 
 // forksrv.rs
 pub static ENABLE_FORKSRV: &str = "ANGORA_ENABLE_FORKSRV";
 pub static FORKSRV_SOCKET_PATH_VAR: &str = "ANGORA_FORKSRV_SOCKET_PATH";
+// Inputs run by a persistent process before the fork server respawns it
+pub static PERSISTENT_MAX_VAR: &str = "ANGORA_PERSISTENT_MAX";
 
 // command.rs
 pub static ANGORA_DIR_NAME: &str = "angora";
 pub static ANGORA_LOG_FILE: &str = "angora.log";
 pub static COND_QUEUE_FILE: &str = "cond_queue.csv";
 pub static CHART_STAT_FILE: &str = "chart_stat.json";
+pub static TARGETS_STATUS_FILE: &str = "targets_status.json";
+pub static TARGETS_CONTROL_FILE: &str = "targets_control";
+pub static TARGETS_CONTROL_LOG_FILE: &str = "targets_control.log";
+pub static CFG_STATE_FILE: &str = "cfg_state.json";
+pub static CFG_EXPORT_JSON_FILE: &str = "cfg_export.json";
+pub static CFG_EXPORT_DOT_FILE: &str = "cfg_export.dot";
 
 pub const SLOW_SPEED: u32 = 888888;
 pub const UNREACHABLE: u64 = std::u64::MAX;
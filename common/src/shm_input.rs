@@ -0,0 +1,14 @@
+// Test case delivered through shared memory instead of the input file,
+// written by fuzzer/src/executor/shm_input.rs and read by
+// runtime_fast/src/shm_input.rs
+use crate::config::INPUT_SHM_SIZE;
+
+#[repr(C)]
+pub struct InputBuf {
+    pub len: u32,
+    pub buf: [u8; INPUT_SHM_SIZE],
+}
+
+// Length of a test case too large for the shared memory, it is only in the
+// input file
+pub const INPUT_IN_FILE: u32 = u32::MAX;
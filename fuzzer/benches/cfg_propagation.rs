@@ -0,0 +1,72 @@
+// Score propagation on large inter-procedural CFGs: building the graph at
+// startup, adding the edges found at runtime and removing a solved target.
+// Run with `cargo bench -p angora`.
+use angora::dyncfg::{
+    cfg::{BbId, CmpId, ControlFlowGraph},
+    fparse::CfgFile,
+    metric::HarmonicMean,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+const NUM_NODES: BbId = 1_000_000;
+const TARGET: CmpId = NUM_NODES * 10;
+
+// Chain of BBs with a branch every 4 BBs and the target at the end
+fn chain(num_nodes: BbId) -> CfgFile {
+    let edges = (1..num_nodes).map(|bb| (bb - 1, bb)).collect();
+    let mut id_mapping: HashMap<BbId, HashSet<CmpId>> = (0..num_nodes - 1)
+        .step_by(4)
+        .map(|bb| (bb, vec![bb + num_nodes].into_iter().collect()))
+        .collect();
+    id_mapping.insert(num_nodes - 1, vec![TARGET].into_iter().collect());
+    CfgFile {
+        targets: vec![TARGET].into_iter().collect(),
+        edges,
+        id_mapping,
+        callsite_dominators: HashMap::new(),
+        flows: vec![],
+        target_info: HashMap::new(),
+        functions: vec![],
+    }
+}
+
+fn bench_startup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cfg_startup");
+    group.sample_size(10);
+    group.bench_function("chain_1m", |b| {
+        b.iter_batched(
+            || chain(NUM_NODES),
+            |data| ControlFlowGraph::new(data, Arc::new(HarmonicMean)),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn bench_runtime(c: &mut Criterion) {
+    let cfg = ControlFlowGraph::new(chain(NUM_NODES), Arc::new(HarmonicMean));
+
+    let mut group = c.benchmark_group("cfg_runtime");
+    group.sample_size(10);
+    // Shortcut edge close to the target, only the nodes before it change
+    group.bench_function("add_edge_1m", |b| {
+        b.iter_batched(
+            || cfg.clone(),
+            |mut cfg| cfg.add_edge((NUM_NODES / 2, NUM_NODES - 2)),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("remove_target_1m", |b| {
+        b.iter_batched(
+            || cfg.clone(),
+            |mut cfg| cfg.remove_target(TARGET),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_startup, bench_runtime);
+criterion_main!(benches);
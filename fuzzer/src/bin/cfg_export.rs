@@ -0,0 +1,98 @@
+#[macro_use]
+extern crate clap;
+use clap::{App, Arg, ArgGroup};
+
+extern crate angora;
+use angora::dyncfg::{
+    cfg::ControlFlowGraph,
+    export::CfgExport,
+    fparse::parse_targets_file,
+    metric::parse_distance_metric,
+    state::read_state,
+};
+use angora::DISTANCE_METRICS;
+use std::{collections::HashMap, fs, io, io::Write, path::Path};
+
+fn main() {
+    let matches = App::new("cfg-export")
+        .version(crate_version!())
+        .about("Exports the CFG built from a targets file, or exported by the fuzzer, as DOT or JSON")
+        .arg(Arg::with_name("cfg_file")
+            .short("c")
+            .long("cfg")
+            .value_name("FILE")
+            .help("Targets file (JSON file)")
+            .takes_value(true))
+        .arg(Arg::with_name("export_file")
+            .short("j")
+            .long("export")
+            .value_name("FILE")
+            .help("CFG exported by the fuzzer (cfg_export.json in the output directory)")
+            .takes_value(true))
+        .group(ArgGroup::with_name("input")
+            .args(&["cfg_file", "export_file"])
+            .required(true))
+        .arg(Arg::with_name("state_file")
+            .short("s")
+            .long("state")
+            .value_name("FILE")
+            .help("CFG state saved by the fuzzer (cfg_state.json in the output directory)")
+            .requires("cfg_file")
+            .takes_value(true))
+        .arg(Arg::with_name("distance_metric")
+            .short("m")
+            .long("distance_metric")
+            .value_name("METRIC")
+            .help("How the distances to the targets are aggregated, default is harmonic")
+            .requires("cfg_file")
+            .possible_values(&DISTANCE_METRICS))
+        .arg(Arg::with_name("format")
+            .short("f")
+            .long("format")
+            .value_name("FORMAT")
+            .help("Output format, default is dot")
+            .possible_values(&["dot", "json"]))
+        .arg(Arg::with_name("reachable")
+            .short("r")
+            .long("reachable")
+            .help("Only export the BBs with a path to a target"))
+        .arg(Arg::with_name("output_file")
+            .short("o")
+            .long("output")
+            .value_name("FILE")
+            .help("Output file, default is stdout")
+            .takes_value(true))
+        .get_matches();
+
+    let mut export = match matches.value_of("export_file") {
+        Some(path) => CfgExport::read_json(Path::new(path))
+            .unwrap_or_else(|e| panic!("Could not read CFG export {}: {:?}", path, e)),
+        None => {
+            let path = matches.value_of("cfg_file").unwrap();
+            let data = parse_targets_file(Path::new(path))
+                .unwrap_or_else(|e| panic!("Could not read cfg targets file {}: {:?}", path, e));
+            let metric = parse_distance_metric(matches.value_of("distance_metric").unwrap_or("harmonic"));
+            let mut cfg = ControlFlowGraph::new(data, metric);
+            if let Some(path) = matches.value_of("state_file") {
+                let state = read_state(Path::new(path))
+                    .unwrap_or_else(|e| panic!("Could not read CFG state {}: {:?}", path, e));
+                cfg.merge_state(state);
+            }
+            // Hit counts are only known by the fuzzer
+            cfg.export(&HashMap::new())
+        },
+    };
+    if matches.is_present("reachable") {
+        export.retain_reachable();
+    }
+
+    let mut out: Box<dyn Write> = match matches.value_of("output_file") {
+        Some(path) => Box::new(io::BufWriter::new(fs::File::create(path).expect("Could not create output file"))),
+        None => Box::new(io::BufWriter::new(io::stdout())),
+    };
+    let res = match matches.value_of("format").unwrap_or("dot") {
+        "json" => export.write_json(&mut out),
+        _ => export.write_dot(&mut out),
+    };
+    res.and_then(|_| out.flush()).expect("Could not write CFG export");
+}
@@ -4,7 +4,8 @@ use clap::{App, Arg};
 
 extern crate angora;
 extern crate angora_common;
-use angora::fuzz_main;
+use angora::{check_targets, fuzz_main, parse_time_limit, SarifInput, DISTANCE_METRICS};
+use std::process;
 
 fn main() {
     let matches = App::new("angora-fuzzer")
@@ -23,14 +24,14 @@ fn main() {
             .value_name("DIR")
             .help("Sets the directory of input seeds, use \"-\" to restart with existing output directory")
             .takes_value(true)
-            .required(true))
+            .required_unless("check_targets"))
         .arg(Arg::with_name("output_dir")
             .short("o")
             .long("output")
             .value_name("DIR")
             .help("Sets the directory of outputs")
             .takes_value(true)
-            .required(true))
+            .required_unless("check_targets"))
         .arg(Arg::with_name("track_target")
             .short("t")
             .long("track")
@@ -45,7 +46,7 @@ fn main() {
             .takes_value(true))
         .arg(Arg::with_name("pargs")
             .help("Targeted program (USE_FAST) and arguments. Any \"@@\" will be substituted with the input filename from Angora.")
-            .required(true)
+            .required_unless("check_targets")
             .multiple(true)
             .allow_hyphen_values(true)
             .last(true)
@@ -60,8 +61,23 @@ fn main() {
             .short("T")
             .long("time_limit")
             .value_name("TIME")
-            .help("time limit for programs, default is 1(s), the tracking timeout is 12 * TIME")
+            .help("time limit for programs in seconds, or in milliseconds with the ms suffix (e.g. 50ms), default is 1(s). Use auto to derive it from the execution time of the seeds")
+            .validator(|t| parse_time_limit(&t).map(|_| ()))
             .takes_value(true))
+        .arg(Arg::with_name("persistent")
+            .long("persistent")
+            .value_name("RUNS")
+            .help("Let targets calling __angora_persistent_loop run up to RUNS inputs per process")
+            .takes_value(true))
+        .arg(Arg::with_name("shm_input")
+            .long("shm_input")
+            .help("Deliver the test cases through shared memory to targets calling __angora_input_buf"))
+        .arg(Arg::with_name("crash_reports")
+            .long("crash_reports")
+            .help("Run every crash again to sort it by the kind of its ASan/MSan/UBSan report"))
+        .arg(Arg::with_name("crash_triage")
+            .long("crash_triage")
+            .help("Run new crashes again with symbolization (on the sanopt program if any) and keep one crash per stack"))
         .arg(Arg::with_name("thread_jobs")
             .short("j")
             .long("jobs")
@@ -91,14 +107,60 @@ fn main() {
             .long("cfg")
             .value_name("TARGET_FILE")
             .help("Input file with targets and cfg (JSON file)")
-            .required(true)
+            .required_unless("sarif")
+            .takes_value(true))
+        .arg(Arg::with_name("sarif")
+            .long("sarif")
+            .value_name("REPORT")
+            .help("Analyzer report (or directory of reports) to build the targets from, instead of the targets file")
+            .requires("sarif_mapping")
+            .conflicts_with("cfg_file")
+            .takes_value(true))
+        .arg(Arg::with_name("sarif_mapping")
+            .long("sarif_mapping")
+            .value_name("LINES_FILE")
+            .help("Line to cmpid/bbid mapping (CSV from -idassign-emit-lines) used with --sarif")
+            .takes_value(true))
+        .arg(Arg::with_name("sarif_cfg")
+            .long("sarif_cfg")
+            .value_name("CFG_FILE")
+            .help("Static CFG edges (CSV from -idassign-emit-cfg) used with --sarif")
+            .requires("sarif")
+            .takes_value(true))
+        .arg(Arg::with_name("sarif_callgraph")
+            .long("sarif_callgraph")
+            .value_name("CALLGRAPH_FILE")
+            .help("Static call graph (CSV from -idassign-emit-callgraph) used with --sarif")
+            .requires("sarif")
             .takes_value(true))
         .arg(Arg::with_name("only_directed")
             .short("D")
             .long("only_directed")
             .help("Only consider CMPs that have a path to one of the targets (Warn: only use if static CFG is sufficient.)"))
+        .arg(Arg::with_name("distance_metric")
+            .long("distance_metric")
+            .value_name("METRIC")
+            .help("How the distances to the targets are aggregated, default is harmonic")
+            .possible_values(&DISTANCE_METRICS))
+        .arg(Arg::with_name("context_distance")
+            .long("context_distance")
+            .help("Adjust the distance of a cond to its calling context, using the callers seen running after it"))
+        .arg(Arg::with_name("check_targets")
+            .long("check_targets")
+            .help("Only validate the targets file (or the targets built from --sarif) and print the problems found"))
         .get_matches();
 
+    let cfg_file = matches.value_of("cfg_file").or(matches.value_of("sarif")).unwrap();
+    let sarif_input = matches.value_of("sarif").map(|report| SarifInput {
+        report,
+        mapping: matches.value_of("sarif_mapping").unwrap(),
+        static_cfg: matches.value_of("sarif_cfg"),
+        call_graph: matches.value_of("sarif_callgraph"),
+    });
+    if matches.is_present("check_targets") {
+        process::exit(if check_targets(cfg_file, sarif_input) { 0 } else { 1 });
+    }
+
     fuzz_main(
         matches.value_of("mode").unwrap_or("llvm"),
         matches.value_of("input_dir").unwrap(),
@@ -107,13 +169,20 @@ fn main() {
         matches.values_of_lossy("pargs").unwrap(),
         value_t!(matches, "thread_jobs", usize).unwrap_or(1),
         value_t!(matches, "memory_limit", u64).unwrap_or(angora_common::config::MEM_LIMIT),
-        value_t!(matches, "time_limit", u64).unwrap_or(angora_common::config::TIME_LIMIT),
+        parse_time_limit(matches.value_of("time_limit").unwrap_or("1")).unwrap(),
         matches.value_of("search_method").unwrap_or("gd"),
         matches.occurrences_of("sync_afl") > 0,
         matches.occurrences_of("disable_afl_mutation") == 0,
         matches.occurrences_of("disable_exploitation") == 0,
-        matches.value_of("cfg_file").unwrap(),
+        cfg_file,
         matches.value_of("sanopt_target"),
         matches.occurrences_of("only_directed") > 0,
+        sarif_input,
+        matches.value_of("distance_metric").unwrap_or("harmonic"),
+        matches.occurrences_of("context_distance") > 0,
+        value_t!(matches, "persistent", u32).unwrap_or(0),
+        matches.occurrences_of("shm_input") > 0,
+        matches.occurrences_of("crash_reports") > 0,
+        matches.occurrences_of("crash_triage") > 0,
     );
 }
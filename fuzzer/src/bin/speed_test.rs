@@ -4,7 +4,7 @@ use angora::executor::Forksrv;
 use std::{collections::HashMap, env, os::unix::io::RawFd, time::SystemTime};
 
 static FUZZER_ID_VAR: &str = "ANGORA_FUZZER_ID";
-const TIME_LIMIT: u64 = 5;
+const TIME_LIMIT: u64 = 5000; // ms
 const MEM_LIMIT: u64 = 2000;
 
 fn main() {
@@ -0,0 +1,33 @@
+#[macro_use]
+extern crate clap;
+use clap::{App, Arg};
+
+extern crate angora;
+use angora::dyncfg::fparse::{parse_targets_file, write_targets_file};
+use std::path::Path;
+
+fn main() {
+    let matches = App::new("targets-convert")
+        .version(crate_version!())
+        .about("Converts a targets file between JSON and the compact binary format, the input format is detected")
+        .arg(Arg::with_name("input_file")
+            .help("Targets file to convert")
+            .required(true)
+            .index(1))
+        .arg(Arg::with_name("output_file")
+            .help("Converted targets file")
+            .required(true)
+            .index(2))
+        .arg(Arg::with_name("json")
+            .short("j")
+            .long("json")
+            .help("Write JSON instead of the compact format"))
+        .get_matches();
+
+    let input = matches.value_of("input_file").unwrap();
+    let output = matches.value_of("output_file").unwrap();
+    let data = parse_targets_file(Path::new(input))
+        .unwrap_or_else(|e| panic!("Could not read cfg targets file: {}", e));
+    write_targets_file(Path::new(output), &data, !matches.is_present("json"))
+        .unwrap_or_else(|e| panic!("Could not write targets file {}: {}", output, e));
+}
@@ -2,6 +2,7 @@ use crate::executor::StatusType;
 use angora_common::{config::BRANCHES_SIZE, shm::SHM};
 use std::{
     self,
+    collections::HashMap,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc, RwLock,
@@ -49,7 +50,9 @@ macro_rules! cast {
 pub struct GlobalBranches {
     virgin_branches: RwLock<Box<BranchBuf>>,
     tmouts_branches: RwLock<Box<BranchBuf>>,
-    crashes_branches: RwLock<Box<BranchBuf>>,
+    // One map per crash category (signal or exit code), so that a new kind
+    // of crash on known edges is not dropped
+    crashes_branches: RwLock<HashMap<String, Arc<RwLock<Box<BranchBuf>>>>>,
     density: AtomicUsize,
     cfg: RwLock<ControlFlowGraph>,
 }
@@ -59,7 +62,7 @@ impl GlobalBranches {
         Self {
             virgin_branches: RwLock::new(Box::new([255u8; BRANCHES_SIZE])),
             tmouts_branches: RwLock::new(Box::new([255u8; BRANCHES_SIZE])),
-            crashes_branches: RwLock::new(Box::new([255u8; BRANCHES_SIZE])),
+            crashes_branches: RwLock::new(HashMap::new()),
             density: AtomicUsize::new(0),
             cfg,
         }
@@ -69,6 +72,18 @@ impl GlobalBranches {
         let d = self.density.load(Ordering::Relaxed);
         (d * 10000 / BRANCHES_SIZE) as f32 / 100.0
     }
+
+    fn crash_map(&self, category: &str) -> Arc<RwLock<Box<BranchBuf>>> {
+        if let Some(map) = self.crashes_branches.read().unwrap().get(category) {
+            return map.clone();
+        }
+        self.crashes_branches
+            .write()
+            .unwrap()
+            .entry(category.to_string())
+            .or_insert_with(|| Arc::new(RwLock::new(Box::new([255u8; BRANCHES_SIZE]))))
+            .clone()
+    }
 }
 
 pub struct Branches {
@@ -123,14 +138,21 @@ impl Branches {
     }
 
     pub fn has_new(&mut self, status: StatusType) -> (bool, bool, usize) {
-        let gb_map = match status {
-            StatusType::Normal => &self.global.virgin_branches,
-            StatusType::Timeout => &self.global.tmouts_branches,
-            StatusType::Crash => &self.global.crashes_branches,
-            _ => {
-                return (false, false, 0);
-            },
-        };
+        match status {
+            StatusType::Normal => self.has_new_in(&self.global.virgin_branches, status),
+            StatusType::Timeout => self.has_new_in(&self.global.tmouts_branches, status),
+            _ => (false, false, 0),
+        }
+    }
+
+    // Crashes are compared to the crashes of the same category only, the
+    // category given by their exit status
+    pub fn has_new_crash(&mut self, category: &str) -> (bool, bool, usize) {
+        let gb_map = self.global.crash_map(category);
+        self.has_new_in(&gb_map, StatusType::Crash)
+    }
+
+    fn has_new_in(&self, gb_map: &RwLock<Box<BranchBuf>>, status: StatusType) -> (bool, bool, usize) {
         let path = self.get_path();
         let edge_num = path.len();
 
@@ -215,4 +237,17 @@ mod tests {
         assert_eq!(path[2].1, COUNT_LOOKUP[3]);
         assert_eq!(br.has_new(StatusType::Normal), (true, true, 3));
     }
+
+    #[test]
+    fn branch_crash_categories() {
+        let global_branches = Arc::new(GlobalBranches::new(RwLock::new( ControlFlowGraph::empty_new())));
+        let mut br = Branches::new(global_branches.clone());
+        br.trace[4] = 1;
+        assert_eq!(br.has_new_crash("sigsegv"), (true, true, 1));
+        assert_eq!(br.has_new_crash("sigsegv"), (false, false, 1));
+        // Same edges, other kind of crash
+        assert_eq!(br.has_new_crash("msan"), (true, true, 1));
+        assert_eq!(br.has_new(StatusType::Normal), (true, true, 1));
+        assert_eq!(global_branches.crashes_branches.read().unwrap().len(), 2);
+    }
 }
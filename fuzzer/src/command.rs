@@ -1,5 +1,5 @@
 use crate::{check_dep, search, tmpfs};
-use angora_common::defs;
+use angora_common::{config, defs};
 use std::{
     env,
     path::{Path, PathBuf},
@@ -45,6 +45,7 @@ pub struct CommandOpt {
     pub is_stdin: bool,
     pub search_method: search::SearchMethod,
     pub mem_limit: u64,
+    // Milliseconds
     pub time_limit: u64,
     pub is_raw: bool,
     pub uses_asan: bool,
@@ -54,6 +55,14 @@ pub struct CommandOpt {
     pub directed_targets_file: String,
     pub sanopt_bin: Option<String>,
     pub directed_only: bool,
+    // Inputs per process in persistent mode, 0 to fork for every input
+    pub persistent_max: u32,
+    // Deliver the test cases through shared memory
+    pub shm_input: bool,
+    // Run crashes again to classify them from their sanitizer report
+    pub crash_reports: bool,
+    // Keep a single crash per symbolized stack
+    pub crash_triage: bool,
 }
 
 pub fn make_absolute(path: &Path) -> PathBuf {
@@ -73,6 +82,34 @@ pub fn make_absolute_str(path_str: &str) -> String {
     path.to_path_buf().to_str().unwrap().to_string()
 }
 
+// Parses a time limit: "auto", milliseconds with the "ms" suffix or seconds.
+// Returns the milliseconds, or None to calibrate the time limit on the seeds.
+pub fn parse_time_limit(t: &str) -> Result<Option<u64>, String> {
+    let ms = if t == "auto" {
+        return Ok(None);
+    } else if let Some(ms) = t.strip_suffix("ms") {
+        ms.parse::<u64>()
+    } else {
+        t.trim_end_matches('s').parse::<u64>().map(|s| s * 1000)
+    };
+    match ms {
+        Ok(0) | Err(_) => Err(format!("Invalid time limit: {}, expected e.g. 1, 2s, 50ms or auto", t)),
+        Ok(ms) => Ok(Some(ms)),
+    }
+}
+
+// AFL-style time limit from the average execution time of the seeds
+pub fn auto_time_limit(avg_exec_us: f32) -> u64 {
+    let ms = (avg_exec_us as u64 * config::AUTO_TIME_LIMIT_FACTOR + 999) / 1000;
+    let rounded = (ms / config::AUTO_TIME_LIMIT_ROUND + 1) * config::AUTO_TIME_LIMIT_ROUND;
+    rounded.min(config::TIME_LIMIT)
+}
+
+// Time limit used to confirm that a timeout is a hang
+pub fn hang_time_limit(time_limit: u64) -> u64 {
+    (time_limit * config::HANG_LIMIT_FACTOR).max(config::TIME_LIMIT)
+}
+
 impl CommandOpt {
     pub fn new(
         mode: &str,
@@ -87,6 +124,10 @@ impl CommandOpt {
         directed_targets_file: &str,
         sanopt_target: Option<&str>,
         directed_only: bool,
+        persistent_max: u32,
+        shm_input: bool,
+        crash_reports: bool,
+        crash_triage: bool,
     ) -> Self {
         let mode = InstrumentationMode::from(mode);
         
@@ -174,6 +215,10 @@ impl CommandOpt {
             directed_targets_file: directed_targets_file.to_string(),
             sanopt_bin,
             directed_only,
+            persistent_max,
+            shm_input,
+            crash_reports,
+            crash_triage,
         }
     }
 
@@ -244,3 +289,24 @@ impl Drop for CommandOpt {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_limits() {
+        assert_eq!(parse_time_limit("2"), Ok(Some(2000)));
+        assert_eq!(parse_time_limit("2s"), Ok(Some(2000)));
+        assert_eq!(parse_time_limit("50ms"), Ok(Some(50)));
+        assert_eq!(parse_time_limit("auto"), Ok(None));
+        assert!(parse_time_limit("0ms").is_err());
+        assert!(parse_time_limit("1.5").is_err());
+
+        assert_eq!(auto_time_limit(300.0), 20);
+        assert_eq!(auto_time_limit(4000.0), 40);
+        assert_eq!(auto_time_limit(500_000.0), config::TIME_LIMIT);
+        assert_eq!(hang_time_limit(40), config::TIME_LIMIT);
+        assert_eq!(hang_time_limit(2000), 4000);
+    }
+}
@@ -0,0 +1,220 @@
+use super::*;
+use crate::dyncfg::cfg::CmpId;
+use angora_common::defs;
+use chrono::prelude::Local;
+use std::{fs, io::prelude::*};
+
+// Command of the targets control file, one JSON object per line, e.g.
+// {"op": "add", "cmpid": 1234, "weight": 3, "label": "a.c:12 nullPointer"}
+// {"op": "retire", "cmpid": 1234}
+// {"op": "reweight", "cmpid": 1234, "weight": 5}
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ControlCommand {
+    Add {
+        cmpid: CmpId,
+        weight: Option<u32>,
+        label: Option<String>,
+    },
+    Retire {
+        cmpid: CmpId,
+    },
+    Reweight {
+        cmpid: CmpId,
+        weight: u32,
+    },
+}
+
+impl Depot {
+    // Applies the commands written to the targets control file of the
+    // output directory since the last poll. The file is consumed, the
+    // outcome of every command is appended to the control log.
+    pub fn poll_control(&self) {
+        let dir = self.dirs.inputs_dir.parent().unwrap();
+        let path = dir.join(defs::TARGETS_CONTROL_FILE);
+        // Take the file first, commands written meanwhile go to a new one. A
+        // file taken but not read by the last poll is applied first.
+        let taken = path.with_extension("applying");
+        if !taken.exists() {
+            if !path.exists() {
+                return;
+            }
+            if let Err(e) = fs::rename(&path, &taken) {
+                warn!("Could not take targets control file: {:?}", e);
+                return;
+            }
+        }
+        let content = match fs::read_to_string(&taken) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Could not read targets control file {:?}, kept for the next poll: {:?}", taken, e);
+                return;
+            },
+        };
+        let _ = fs::remove_file(&taken);
+
+        let mut outcomes = vec![];
+        let mut changed = false;
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let outcome = match serde_json::from_str::<ControlCommand>(line) {
+                Ok(cmd) => {
+                    let (applied, outcome) = self.apply_control(cmd);
+                    changed |= applied;
+                    outcome
+                },
+                Err(e) => format!("rejected: {}", e),
+            };
+            info!("Targets control: {} -> {}", line, outcome);
+            outcomes.push(format!("{} {} -> {}", Local::now().to_rfc3339(), line, outcome));
+        }
+
+        if changed {
+            self.rerank();
+        }
+        let log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(defs::TARGETS_CONTROL_LOG_FILE));
+        match log {
+            Ok(mut f) => {
+                for o in outcomes {
+                    let _ = writeln!(f, "{}", o);
+                }
+            },
+            Err(e) => warn!("Could not write targets control log: {:?}", e),
+        }
+    }
+
+    fn apply_control(&self, cmd: ControlCommand) -> (bool, String) {
+        let mut cfg = self.cfg.write().unwrap();
+        match cmd {
+            ControlCommand::Add { cmpid, weight, label } => {
+                if cfg.get_bb_from_cmp(&cmpid).is_none() {
+                    return (false, "rejected: unknown cmpid".to_string());
+                }
+                if weight.is_some() || label.is_some() {
+                    cfg.set_target_info(cmpid, weight, label);
+                }
+                if cfg.add_target(cmpid) {
+                    (true, "added".to_string())
+                } else if cfg.is_solved(cmpid) {
+                    (weight.is_some(), "already solved".to_string())
+                } else {
+                    (weight.is_some(), "already a target".to_string())
+                }
+            },
+            ControlCommand::Retire { cmpid } => {
+                if cfg.retire_target(cmpid) {
+                    (true, "retired".to_string())
+                } else {
+                    (false, "rejected: not a target".to_string())
+                }
+            },
+            ControlCommand::Reweight { cmpid, weight } => {
+                if !cfg.is_target(cmpid) {
+                    return (false, "rejected: not a target".to_string());
+                }
+                cfg.set_target_info(cmpid, Some(weight), None);
+                (true, format!("weight {}", weight))
+            },
+        }
+    }
+
+    // Recomputes the priority of the queued conds after the targets changed
+    fn rerank(&self) {
+        let mut q = match self.queue.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                warn!("Mutex poisoned! Results may be incorrect. Continuing...");
+                poisoned.into_inner()
+            },
+        };
+        let cfg = self.cfg.read().unwrap();
+
+        let mut updates = vec![];
+        for (cond, p) in q.iter() {
+            if p.is_done() || cond.base.is_afl() {
+                continue;
+            }
+            if let Some(&bbid) = cfg.get_bb_from_cmp(&cond.base.cmpid) {
+                let distance = cfg.score_for_context(bbid, cond.base.cmpid, cond.base.context, cond.variables.clone());
+                let p = p.new_distance(distance).with_weight(cfg.target_weight(cond.base.cmpid));
+                updates.push((cond.clone(), p, cfg.is_target(cond.base.cmpid)));
+            }
+        }
+        for (cond, p, is_target) in updates {
+            if let Some(v) = q.get_mut(&cond) {
+                v.0.set_target(is_target);
+            }
+            q.change_priority(&cond, p);
+        }
+        info!("Re-ranked the depot after the targets changed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cond_stmt::CondStmt, dyncfg::{cfg::ControlFlowGraph, fparse::CfgFile, metric::HarmonicMean}};
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn control_targets() {
+        let out_dir = std::env::temp_dir().join(format!("angora_control_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out_dir);
+        fs::create_dir(&out_dir).unwrap();
+
+        let data = CfgFile {
+            targets: vec![1100].into_iter().collect(),
+            edges: vec![(0,10), (10,20), (0,30)].into_iter().collect(),
+            id_mapping: [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (30, vec![1200].into_iter().collect())].iter().cloned().collect(),
+            ..Default::default()
+        };
+        let cfg = ControlFlowGraph::new(data, Arc::new(HarmonicMean));
+        let depot = Depot::new(out_dir.join("seeds"), &out_dir, RwLock::new(cfg));
+        let mut cond = CondStmt::new();
+        cond.base.cmpid = 1200;
+        depot.add_entries(vec![cond.clone()]);
+
+        fs::write(out_dir.join(defs::TARGETS_CONTROL_FILE), concat!(
+            "{\"op\": \"add\", \"cmpid\": 1200, \"weight\": 3, \"label\": \"a.c:12\"}\n",
+            "{\"op\": \"retire\", \"cmpid\": 1100}\n",
+            "{\"op\": \"reweight\", \"cmpid\": 4242, \"weight\": 2}\n",
+            "{\"op\": \"solve\"}\n")).unwrap();
+        depot.poll_control();
+
+        {
+            let cfg = depot.cfg.read().unwrap();
+            assert_eq!(cfg.is_target(1200), true);
+            assert_eq!(cfg.target_weight(1200), 3);
+            assert_eq!(cfg.is_target(1100), false);
+            assert_eq!(cfg.is_solved(1100), false);
+            assert_eq!(cfg.has_path_to_target(1000), false);
+        }
+        let q = depot.queue.lock().unwrap();
+        let (queued, p) = q.get(&cond).unwrap();
+        assert_eq!(queued.is_target, true);
+        assert_eq!(*p, QPriority::init_distance(0, 0).with_weight(3));
+        drop(q);
+
+        assert!(!out_dir.join(defs::TARGETS_CONTROL_FILE).exists());
+        let log = fs::read_to_string(out_dir.join(defs::TARGETS_CONTROL_LOG_FILE)).unwrap();
+        let outcomes: Vec<&str> = log.lines().map(|l| l.rsplit(" -> ").next().unwrap()).collect();
+        assert_eq!(outcomes[..3], ["added", "retired", "rejected: not a target"]);
+        assert!(outcomes[3].starts_with("rejected: "));
+
+        // A file taken but not read by a poll is applied by the next one
+        let control = out_dir.join(defs::TARGETS_CONTROL_FILE);
+        fs::write(control.with_extension("applying"), "{\"op\": \"retire\", \"cmpid\": 1200}\n").unwrap();
+        fs::write(&control, "{\"op\": \"add\", \"cmpid\": 1100}\n").unwrap();
+        depot.poll_control();
+        assert_eq!(depot.cfg.read().unwrap().is_target(1200), false);
+        assert_eq!(depot.cfg.read().unwrap().is_target(1100), false);
+        depot.poll_control();
+        assert_eq!(depot.cfg.read().unwrap().is_target(1100), true);
+        assert!(!control.exists());
+
+        drop(depot);
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use super::*;
+use crate::{dyncfg::state::rebase_input, executor::{CrashKind, CrashStack}};
+use angora_common::defs;
+use std::{collections::HashMap, fs, io, path::Path};
+
+// Entry of crashes/index.json, one per unique stack
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CrashIndexEntry {
+    pub hash: String,
+    // Top frames of the stack, the ones hashed
+    pub frames: Vec<String>,
+    pub message: Option<String>,
+    // Crashes with this stack, only the first one is saved
+    pub count: usize,
+    // Saved crash, relative to the output directory
+    pub input: String,
+}
+
+#[derive(Debug, Default)]
+pub struct CrashIndex {
+    entries: HashMap<String, CrashIndexEntry>,
+}
+
+impl Depot {
+    // Saves a crash unless another crash has the same stack, returns its id
+    // if it was saved
+    pub fn save_unique_crash(
+        &self,
+        buf: &Vec<u8>,
+        cmpid: u32,
+        kind: &CrashKind,
+        stack: &CrashStack,
+    ) -> Option<usize> {
+        let hash = stack.hash();
+        let mut index = self.crash_index.lock().unwrap();
+        if let Some(entry) = index.entries.get_mut(&hash) {
+            entry.count += 1;
+            debug!("Duplicate of crash {} ({})", entry.input, hash);
+            self.dump_crash_index(&index);
+            return None;
+        }
+
+        let id = self.save_crash(buf, cmpid, kind);
+        let dir = self.dirs.crashes_dir.join(kind.category());
+        let input = get_file_name(&dir, id);
+        let stack_path = input.with_extension(defs::CRASH_STACK_EXT);
+        if let Err(e) = fs::write(&stack_path, stack.to_string()) {
+            warn!("Could not write crash stack {:?}: {:?}", stack_path, e);
+        }
+
+        let out_dir = self.dirs.crashes_dir.parent().unwrap();
+        let relative = input.strip_prefix(out_dir).unwrap_or(&input);
+        index.entries.insert(
+            hash.clone(),
+            CrashIndexEntry {
+                hash,
+                frames: stack.top_frames().to_vec(),
+                message: stack.message.clone(),
+                count: 1,
+                input: relative.display().to_string(),
+            },
+        );
+        self.dump_crash_index(&index);
+        Some(id)
+    }
+
+    // Loads the index of a previous run, whose output directory is `dir`,
+    // so that the crashes it saved are not saved again
+    pub fn load_crash_index(&self, dir: &Path) {
+        let path = dir.join(defs::CRASHES_DIR).join(defs::CRASH_INDEX_FILE);
+        if !path.exists() {
+            return;
+        }
+        let entries: Vec<CrashIndexEntry> = match fs::read_to_string(&path)
+            .and_then(|c| serde_json::from_str(&c).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+        {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Could not read crash index {:?}: {:?}", path, e);
+                return;
+            },
+        };
+        // The inputs stay in the output directory of the previous run
+        let prev_dir = Path::new("..").join(dir.file_name().unwrap());
+        let mut index = self.crash_index.lock().unwrap();
+        for mut entry in entries {
+            rebase_input(&mut entry.input, &prev_dir);
+            index.entries.entry(entry.hash.clone()).or_insert(entry);
+        }
+        info!("Loaded {} unique crash stacks from {:?}", index.entries.len(), path);
+        self.dump_crash_index(&index);
+    }
+
+    // Written to a temporary file first, so that a reader never sees a
+    // partial index
+    fn dump_crash_index(&self, index: &CrashIndex) {
+        let mut entries: Vec<&CrashIndexEntry> = index.entries.values().collect();
+        entries.sort_by(|a, b| a.input.cmp(&b.input));
+        let path = self.dirs.crashes_dir.join(defs::CRASH_INDEX_FILE);
+        let tmp = path.with_extension("tmp");
+        let res = fs::File::create(&tmp)
+            .and_then(|f| {
+                serde_json::to_writer_pretty(io::BufWriter::new(f), &entries)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            })
+            .and_then(|_| fs::rename(&tmp, &path));
+        if let Err(e) = res {
+            warn!("Could not write crash index {:?}: {:?}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dyncfg::cfg::ControlFlowGraph;
+    use crate::executor::ExitInfo;
+    use std::sync::RwLock;
+
+    #[test]
+    fn crash_dedup() {
+        let out_dir = std::env::temp_dir().join(format!("angora_crash_index_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out_dir);
+        fs::create_dir(&out_dir).unwrap();
+        let depot = Depot::new(out_dir.join("seeds"), &out_dir, RwLock::new(ControlFlowGraph::empty_new()));
+
+        let kind = CrashKind::new(ExitInfo { signal: Some(libc::SIGSEGV), exit_code: None }, false);
+        let stack = |f: &str| CrashStack { message: None, frames: vec![f.to_string(), "main main.c:3".to_string()] };
+        assert_eq!(depot.save_unique_crash(&vec![1], 0, &kind, &stack("parse parse.c:12")), Some(0));
+        assert_eq!(depot.save_unique_crash(&vec![2], 0, &kind, &stack("parse parse.c:12")), None);
+        assert_eq!(depot.save_unique_crash(&vec![3], 0, &kind, &stack("parse parse.c:14")), Some(1));
+
+        let crashes = out_dir.join(defs::CRASHES_DIR);
+        assert!(crashes.join("sigsegv/id:000000.stack").exists());
+        assert!(!crashes.join("sigsegv/id:000002").exists());
+        let index: serde_json::Value =
+            serde_json::from_reader(fs::File::open(crashes.join(defs::CRASH_INDEX_FILE)).unwrap()).unwrap();
+        assert_eq!(index.as_array().unwrap().len(), 2);
+        assert_eq!(index[0]["input"], "crashes/sigsegv/id:000000");
+        assert_eq!(index[0]["count"], 2);
+        assert_eq!(index[1]["count"], 1);
+
+        // A resumed run knows the stacks of the previous one
+        let next_dir = out_dir.with_extension("next");
+        let _ = fs::remove_dir_all(&next_dir);
+        fs::create_dir(&next_dir).unwrap();
+        let resumed = Depot::new(next_dir.join("seeds"), &next_dir, RwLock::new(ControlFlowGraph::empty_new()));
+        resumed.load_crash_index(&out_dir);
+        assert_eq!(resumed.save_unique_crash(&vec![4], 0, &kind, &stack("parse parse.c:14")), None);
+        let index: serde_json::Value =
+            serde_json::from_reader(fs::File::open(next_dir.join(defs::CRASHES_DIR).join(defs::CRASH_INDEX_FILE)).unwrap()).unwrap();
+        let prev_input = format!("../{}/crashes/sigsegv/id:000000", out_dir.file_name().unwrap().to_str().unwrap());
+        assert_eq!(index[0]["input"], prev_input.as_str());
+        assert_eq!(index[1]["count"], 2);
+
+        drop(resumed);
+        drop(depot);
+        fs::remove_dir_all(&next_dir).unwrap();
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
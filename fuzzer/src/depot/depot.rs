@@ -1,5 +1,8 @@
 use super::*;
-use crate::{cond_stmt::CondStmt, executor::StatusType};
+use crate::{
+    cond_stmt::CondStmt,
+    executor::{CrashKind, StatusType},
+};
 use crate::dyncfg::cfg::ControlFlowGraph;
 use rand;
 use std::{
@@ -23,6 +26,8 @@ pub struct Depot {
     pub num_crashes: AtomicUsize,
     pub dirs: DepotDir,
     pub cfg: RwLock<ControlFlowGraph>,
+    pub targets_log: Mutex<TargetsLog>,
+    pub crash_index: Mutex<CrashIndex>,
 }
 
 impl Depot {
@@ -33,7 +38,9 @@ impl Depot {
             num_hangs: AtomicUsize::new(0),
             num_crashes: AtomicUsize::new(0),
             dirs: DepotDir::new(in_dir, out_dir),
-            cfg
+            cfg,
+            targets_log: Mutex::new(TargetsLog::default()),
+            crash_index: Mutex::new(CrashIndex::default()),
         }
     }
 
@@ -67,17 +74,29 @@ impl Depot {
             StatusType::Timeout => {
                 Self::save_input(&status, buf, &self.num_hangs, cmpid, &self.dirs.hangs_dir)
             },
-            StatusType::Crash => Self::save_input(
-                &status,
-                buf,
-                &self.num_crashes,
-                cmpid,
-                &self.dirs.crashes_dir,
-            ),
+            // Crashes need their kind, see save_crash
             _ => 0,
         }
     }
 
+    // Crashes are saved in a subdirectory per category
+    pub fn save_crash(&self, buf: &Vec<u8>, cmpid: u32, kind: &CrashKind) -> usize {
+        let category = kind.category();
+        let dir = self.dirs.crashes_dir.join(&category);
+        if !dir.exists() {
+            fs::create_dir_all(&dir).expect("Could not create crash directory.");
+        }
+        let id = Self::save_input(&StatusType::Crash, buf, &self.num_crashes, cmpid, &dir);
+        info!(
+            "Crash {} in {}: signal {:?}, exit code {:?}, sanitizer {:?}",
+            id, category, kind.signal, kind.exit_code, kind.sanitizer
+        );
+        if self.cfg.read().unwrap().is_target(cmpid) {
+            self.log_crash(cmpid, id, &category);
+        }
+        id
+    }
+
     pub fn empty(&self) -> bool {
         self.num_inputs.load(Ordering::Relaxed) == 0
     }
@@ -125,9 +144,10 @@ impl Depot {
                 let cfg = self.cfg.read().unwrap();
                 //let distance = cfg.score_for_cmp(cond.base.cmpid);
                 let mut distance = std::u32::MAX;
+                let weight = cfg.target_weight(cond.base.cmpid);
                 if !cond.base.is_afl() {
                     if let Some(&bbid) = cfg.get_bb_from_cmp(&cond.base.cmpid) {
-                        distance = cfg.score_for_bb_inp(bbid, cond.variables.clone());
+                        distance = cfg.score_for_context(bbid, cond.base.cmpid, cond.base.context, cond.variables.clone());
                     }
                     else {
                         warn!("CFG warning: cannot find bb containing cmpid {:?}", cond.base.cmpid);
@@ -149,13 +169,13 @@ impl Depot {
                             // If the cond is faster than the older one, we prefer the faster,
                             if config::PREFER_FAST_COND && v.0.speed > cond.speed {
                                 mem::swap(v.0, &mut cond);
-                                let priority = QPriority::init_distance(cond.base.op, distance);
+                                let priority = QPriority::init_distance(cond.base.op, distance).with_weight(weight);
                                 q.change_priority(&cond, priority);
                             }
                         }
                     }
                 } else {
-                    let priority = QPriority::init_distance(cond.base.op, distance);
+                    let priority = QPriority::init_distance(cond.base.op, distance).with_weight(weight);
                     q.push(cond, priority);
                 }
             }
@@ -175,7 +195,7 @@ impl Depot {
                 v.0.clone_from(&cond);
                 let cfg = self.cfg.read().unwrap();
                 if let Some(&bbid) = cfg.get_bb_from_cmp(&cond.base.cmpid) {
-                    let distance = cfg.score_for_bb(bbid);
+                    let distance = cfg.score_for_context(bbid, cond.base.cmpid, cond.base.context, vec![]);
                     let p = v.1.new_distance(distance);
                     q.change_priority(&cond, p);
                 }
@@ -1,11 +1,47 @@
 use super::*;
+use crate::dyncfg::state::write_state;
 use angora_common::defs;
-use std::{fs, io::prelude::*};
+use std::{fs, io::{prelude::*, BufWriter}};
+
+impl Depot {
+    // Saves what was learned about the CFG, merged back on resume
+    pub fn dump_cfg_state(&self) {
+        let dir = self.dirs.inputs_dir.parent().unwrap();
+        let mut state = self.cfg.read().unwrap().get_state();
+        state.targets_log = self.get_targets_log_state();
+        if let Err(e) = write_state(&dir.join(defs::CFG_STATE_FILE), &state) {
+            warn!("Could not write CFG state: {:?}", e);
+        }
+    }
+
+    // Exports the CFG for visualization, the DOT graph only keeps the BBs
+    // leading to a target so that it stays readable
+    pub fn dump_cfg_export(&self) {
+        let dir = self.dirs.inputs_dir.parent().unwrap();
+        // The executors lock the CFG before the log, never hold both here
+        let hits = self.targets_log.lock().unwrap().hits.clone();
+        let mut export = self.cfg.read().unwrap().export(&hits);
+        let res = fs::File::create(dir.join(defs::CFG_EXPORT_JSON_FILE))
+            .and_then(|f| export.write_json(&mut BufWriter::new(f)));
+        if let Err(e) = res {
+            warn!("Could not write CFG export: {:?}", e);
+        }
+        export.retain_reachable();
+        let res = fs::File::create(dir.join(defs::CFG_EXPORT_DOT_FILE))
+            .and_then(|f| export.write_dot(&mut BufWriter::new(f)));
+        if let Err(e) = res {
+            warn!("Could not write CFG export: {:?}", e);
+        }
+    }
+}
 
 impl Drop for Depot {
     fn drop(&mut self) {
         info!("dump constraints and chart..");
         let dir = self.dirs.inputs_dir.parent().unwrap();
+        self.dump_targets_status();
+        self.dump_cfg_state();
+        self.dump_cfg_export();
 
         let mut log_q = fs::File::create(dir.join(defs::COND_QUEUE_FILE)).unwrap();
         writeln!(
@@ -1,9 +1,12 @@
+mod control;
+mod crash_index;
 mod depot;
 mod depot_dir;
 mod dump;
 mod file;
 mod qpriority;
+mod status;
 mod sync;
 
 pub use self::{depot::Depot, file::*, sync::*};
-use self::{depot_dir::DepotDir, qpriority::QPriority};
+use self::{crash_index::CrashIndex, depot_dir::DepotDir, qpriority::QPriority, status::TargetsLog};
@@ -5,9 +5,11 @@ const INIT_PRIORITY: u16 = 0;
 const AFL_INIT_PRIORITY: u16 = 0;
 const DONE_PRIORITY: u16 = std::u16::MAX;
 const INIT_DISTANCE: u32 = std::u32::MAX;
+const INIT_WEIGHT: u32 = 1;
 
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
-pub struct QPriority(u16, u32);
+// Times fuzzed, distance to the targets and weight of the target
+pub struct QPriority(u16, u32, u32);
 impl QPriority {
     pub fn inc(&self, op: u32) -> Self {
         if op == defs::COND_AFL_OP {
@@ -18,15 +20,15 @@ impl QPriority {
     }
 
     pub fn new_distance(&self, distance: u32) -> Self {
-        QPriority(self.0, distance)
+        QPriority(self.0, distance, self.2)
     }
 
     fn base_inc(&self) -> Self {
-        QPriority(self.0 + 1, self.1)
+        QPriority(self.0 + 1, self.1, self.2)
     }
 
     fn afl_inc(&self) -> Self {
-        QPriority(self.0 + 2, self.1)
+        QPriority(self.0 + 2, self.1, self.2)
     }
 
     pub fn init(op: u32) -> Self {
@@ -42,16 +44,28 @@ impl QPriority {
     }
 
 
+    // A target of weight w is fuzzed w times as often as the other conds
+    pub fn with_weight(&self, weight: u32) -> Self {
+        QPriority(self.0, self.1, weight.max(1))
+    }
+
+    fn rounds(&self) -> u32 {
+        if self.is_done() {
+            return std::u32::MAX;
+        }
+        self.0 as u32 / self.2
+    }
+
     fn base_init(distance: u32) -> Self {
-        QPriority(INIT_PRIORITY, distance)
+        QPriority(INIT_PRIORITY, distance, INIT_WEIGHT)
     }
 
     fn afl_init(distance: u32) -> Self {
-        QPriority(AFL_INIT_PRIORITY, distance)
+        QPriority(AFL_INIT_PRIORITY, distance, INIT_WEIGHT)
     }
 
     pub fn done() -> Self {
-        QPriority(DONE_PRIORITY, INIT_DISTANCE)
+        QPriority(DONE_PRIORITY, INIT_DISTANCE, INIT_WEIGHT)
     }
 
     pub fn is_done(&self) -> bool {
@@ -59,18 +73,14 @@ impl QPriority {
     }
 }
 
-// Make the queue get smallest priority first.
+// Make the queue get smallest priority first, then the closest to the
+// targets, then the heaviest target.
 impl Ord for QPriority {
     fn cmp(&self, other: &QPriority) -> Ordering {
-        match self.0.cmp(&other.0) {
-            Ordering::Greater => Ordering::Less,
-            Ordering::Less => Ordering::Greater,
-            Ordering::Equal => match self.1.cmp(&other.1) {
-                Ordering::Greater => Ordering::Less,
-                Ordering::Less => Ordering::Greater,
-                Ordering::Equal => Ordering::Equal,
-            }
-        }
+        other.rounds().cmp(&self.rounds())
+            .then(other.1.cmp(&self.1))
+            .then(other.0.cmp(&self.0))
+            .then(self.2.cmp(&other.2))
     }
 }
 
@@ -85,3 +95,20 @@ impl fmt::Display for QPriority {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qpriority_weight() {
+        let light = QPriority::init_distance(0, 3);
+        let heavy = QPriority::init_distance(0, 3).with_weight(3);
+        assert!(heavy > light);
+        // Fuzzed twice, the heavy target is still preferred
+        assert!(heavy.inc(0).inc(0) > light.inc(0));
+        assert!(heavy.inc(0).inc(0).inc(0) < light);
+        assert!(QPriority::done() < light.inc(0));
+        assert!(QPriority::init_distance(0, 2) > light);
+    }
+}
@@ -0,0 +1,231 @@
+use super::*;
+use crate::dyncfg::{cfg::CmpId, state::TargetsLogState};
+use angora_common::defs;
+use std::{collections::{HashMap, HashSet}, fs, io, path::Path, time::{Duration, Instant}};
+
+// Entry of targets_status.json, read back by `parser annotate` to link the
+// results of the fuzzer to the static findings
+#[derive(Serialize, Debug)]
+pub struct TargetStatus {
+    pub cmpid: CmpId,
+    pub weight: u32,
+    pub label: Option<String>,
+    pub reached: bool,
+    pub solved: bool,
+    // Edges from the closest BB hit by an input to the target in the
+    // current CFG, 0 once reached
+    pub distance: Option<u32>,
+    // Seconds since the start of the fuzzer
+    pub first_reached: Option<u64>,
+    // Tracked inputs hitting the target
+    pub hits: usize,
+    pub crashed: bool,
+    // No BB hit by an input has a path to the target
+    pub unreachable: bool,
+    // First input reaching the target, relative to the output directory
+    pub witness: Option<String>,
+    // Crashes found while fuzzing the target
+    pub crashes: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct TargetsLog {
+    start_time: Instant,
+    // First input reaching each target, relative to the output directory,
+    // and when
+    witnesses: HashMap<CmpId, (String, u64)>,
+    // Crashing inputs, relative to the output directory
+    crashes: HashMap<CmpId, Vec<String>>,
+    // Tracked inputs hitting each cmp
+    pub hits: HashMap<CmpId, usize>,
+}
+
+impl Default for TargetsLog {
+    fn default() -> Self {
+        TargetsLog {
+            start_time: Instant::now(),
+            witnesses: HashMap::new(),
+            crashes: HashMap::new(),
+            hits: HashMap::new(),
+        }
+    }
+}
+
+fn relative_name(dir: &str, id: usize) -> String {
+    get_file_name(Path::new(dir), id).display().to_string()
+}
+
+impl Depot {
+    // Returns true the first time the target is reached
+    pub fn log_reached(&self, cmpid: CmpId, id: usize) -> bool {
+        let mut log = self.targets_log.lock().unwrap();
+        if log.witnesses.contains_key(&cmpid) {
+            return false;
+        }
+        let elapsed = log.start_time.elapsed().as_secs();
+        log.witnesses.insert(cmpid, (relative_name(defs::INPUTS_DIR, id), elapsed));
+        true
+    }
+
+    pub fn log_hits(&self, cmpids: &HashSet<CmpId>) {
+        let mut log = self.targets_log.lock().unwrap();
+        for &cmpid in cmpids {
+            *log.hits.entry(cmpid).or_insert(0) += 1;
+        }
+    }
+
+    pub fn log_crash(&self, cmpid: CmpId, id: usize, category: &str) {
+        let name = relative_name(&format!("{}/{}", defs::CRASHES_DIR, category), id);
+        let mut log = self.targets_log.lock().unwrap();
+        log.crashes.entry(cmpid).or_insert(vec![]).push(name);
+    }
+
+    // Saved along with the CFG state
+    pub fn get_targets_log_state(&self) -> TargetsLogState {
+        let log = self.targets_log.lock().unwrap();
+        let mut state = TargetsLogState {
+            elapsed: log.start_time.elapsed().as_secs(),
+            witnesses: log.witnesses.iter().map(|(&c, (input, t))| (c, input.clone(), *t)).collect(),
+            crashes: log.crashes.iter().map(|(&c, inputs)| (c, inputs.clone())).collect(),
+            hits: log.hits.iter().map(|(&c, &n)| (c, n)).collect(),
+        };
+        state.witnesses.sort();
+        state.crashes.sort();
+        state.hits.sort();
+        state
+    }
+
+    // Merges the log of a previous run, the campaign goes on from the time
+    // it stopped
+    pub fn merge_targets_log(&self, state: TargetsLogState) {
+        let mut log = self.targets_log.lock().unwrap();
+        if let Some(start) = Instant::now().checked_sub(Duration::from_secs(state.elapsed)) {
+            log.start_time = start;
+        }
+        for (cmpid, input, t) in state.witnesses {
+            log.witnesses.entry(cmpid).or_insert((input, t));
+        }
+        for (cmpid, inputs) in state.crashes {
+            log.crashes.entry(cmpid).or_insert(vec![]).extend(inputs);
+        }
+        for (cmpid, n) in state.hits {
+            *log.hits.entry(cmpid).or_insert(0) += n;
+        }
+    }
+
+    pub fn get_targets_status(&self) -> Vec<TargetStatus> {
+        let cfg = self.cfg.read().unwrap();
+        let log = self.targets_log.lock().unwrap();
+        let mut targets: Vec<CmpId> = cfg.get_targets().into_iter().collect();
+        targets.sort();
+        let hit_bbs = log.hits.keys().filter_map(|c| cfg.get_bb_from_cmp(c)).cloned().collect();
+        let distances = cfg.hop_distances(hit_bbs);
+
+        targets
+            .into_iter()
+            .map(|cmpid| {
+                let witness = log.witnesses.get(&cmpid);
+                let reached = witness.is_some() || cfg.is_solved(cmpid) || cfg.is_reached_waypoint(cmpid);
+                let distance = if reached {
+                    Some(0)
+                } else {
+                    cfg.get_bb_from_cmp(&cmpid).and_then(|bb| distances.get(bb)).cloned()
+                };
+                let crashes = log.crashes.get(&cmpid).cloned().unwrap_or_default();
+                TargetStatus {
+                    cmpid,
+                    weight: cfg.target_weight(cmpid),
+                    label: cfg.target_label(cmpid).map(|l| l.to_string()),
+                    reached,
+                    solved: cfg.is_solved(cmpid),
+                    distance,
+                    first_reached: witness.map(|&(_, t)| t),
+                    hits: log.hits.get(&cmpid).cloned().unwrap_or(0),
+                    crashed: !crashes.is_empty(),
+                    unreachable: distance.is_none(),
+                    witness: witness.map(|(input, _)| input.clone()),
+                    crashes,
+                }
+            })
+            .collect()
+    }
+
+    pub fn dump_targets_status(&self) {
+        let dir = self.dirs.inputs_dir.parent().unwrap();
+        let status = self.get_targets_status();
+        // Written to a temporary file first, so that `parser annotate` never
+        // reads a partial status
+        let path = dir.join(defs::TARGETS_STATUS_FILE);
+        let tmp = path.with_extension("tmp");
+        let res = fs::File::create(&tmp)
+            .and_then(|f| {
+                serde_json::to_writer(io::BufWriter::new(f), &status)
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            })
+            .and_then(|_| fs::rename(&tmp, &path));
+        if let Err(e) = res {
+            warn!("Could not write targets status: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dyncfg::{cfg::ControlFlowGraph, fparse::CfgFile, metric::HarmonicMean};
+    use std::sync::{Arc, RwLock};
+
+    #[test]
+    fn targets_status() {
+        let out_dir = std::env::temp_dir().join(format!("angora_status_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&out_dir);
+        fs::create_dir(&out_dir).unwrap();
+
+        let data = CfgFile {
+            targets: vec![1100, 1200, 1300].into_iter().collect(),
+            edges: vec![(0,10), (10,20), (20,30), (40,50)].into_iter().collect(),
+            id_mapping: [(0, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (30, vec![1200].into_iter().collect()), (50, vec![1300].into_iter().collect())].iter().cloned().collect(),
+            ..Default::default()
+        };
+        let cfg = ControlFlowGraph::new(data, Arc::new(HarmonicMean));
+        let depot = Depot::new(out_dir.join("seeds"), &out_dir, RwLock::new(cfg));
+
+        depot.log_hits(&vec![1000].into_iter().collect());
+        depot.log_hits(&vec![1000, 1100].into_iter().collect());
+        assert_eq!(depot.log_reached(1100, 1), true);
+        assert_eq!(depot.log_reached(1100, 2), false);
+        depot.log_crash(1100, 0, "sigsegv");
+
+        let status = depot.get_targets_status();
+        let summary: Vec<(CmpId, bool, Option<u32>, usize, bool, bool)> = status.iter()
+            .map(|s| (s.cmpid, s.reached, s.distance, s.hits, s.crashed, s.unreachable))
+            .collect();
+        assert_eq!(summary, vec![
+            (1100, true, Some(0), 1, true, false),
+            (1200, false, Some(1), 0, false, false),
+            (1300, false, None, 0, false, true),
+        ]);
+        assert_eq!(status[0].first_reached, Some(0));
+        assert_eq!(status[0].witness.as_deref(), Some("queue/id:000001"));
+        assert_eq!(status[0].crashes, vec!["crashes/sigsegv/id:000000"]);
+
+        // The log goes on in the output directory of the resumed run
+        let mut state = depot.get_targets_log_state();
+        state.rebase(Path::new("../prev"));
+        let next_dir = out_dir.join("next");
+        fs::create_dir(&next_dir).unwrap();
+        let resumed = Depot::new(next_dir.join("seeds"), &next_dir, RwLock::new(ControlFlowGraph::empty_new()));
+        resumed.log_hits(&vec![1000].into_iter().collect());
+        resumed.merge_targets_log(state);
+        assert_eq!(resumed.log_reached(1100, 3), false);
+        let log = resumed.targets_log.lock().unwrap();
+        assert_eq!(log.hits[&1000], 3);
+        assert_eq!(log.witnesses[&1100].0, "../prev/queue/id:000001");
+        assert_eq!(log.crashes[&1100], vec!["../prev/crashes/sigsegv/id:000000"]);
+        drop(log);
+
+        drop(resumed);
+        drop(depot);
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
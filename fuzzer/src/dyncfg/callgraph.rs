@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use super::cfg::{BbId, Score, UNDEF_SCORE};
+use super::fparse::FunctionInfo;
+
+// Factor of the function level distance in the distance of a BB calling
+// functions, the constant c of AFLGo
+const CALL_FACTOR: f64 = 10.0;
+
+type FuncId = usize;
+
+// Static call graph of the targets file, for the function level distance of
+// AFLGo: the distance of a function is the harmonic mean, over the functions
+// of the targets it reaches through calls, of 1 + the number of calls to
+// reach them
+#[derive(Clone, Default)]
+pub struct CallGraph {
+    // Function of every BB
+    functions: HashMap<BbId, FuncId>,
+    // Functions called by every calling BB
+    calls: HashMap<BbId, Vec<FuncId>>,
+    // Functions calling every function
+    callers: Vec<HashSet<FuncId>>,
+    // BBs calling every function
+    callsites: Vec<Vec<BbId>>,
+    distances: Vec<Option<f64>>,
+}
+
+impl CallGraph {
+    // Calls to functions missing from the call graph are dropped
+    pub fn new(infos: &[FunctionInfo]) -> CallGraph {
+        let ids: HashMap<&str, FuncId> = infos.iter().enumerate().map(|(i, f)| (f.name.as_str(), i)).collect();
+        let mut graph = CallGraph {
+            callers: vec![HashSet::new(); infos.len()],
+            callsites: vec![vec![]; infos.len()],
+            distances: vec![None; infos.len()],
+            ..Default::default()
+        };
+        for (id, info) in infos.iter().enumerate() {
+            for &bb in &info.bbs {
+                graph.functions.insert(bb, id);
+            }
+            for (&bb, callees) in &info.calls {
+                let mut callees: Vec<FuncId> = callees.iter().filter_map(|c| ids.get(c.as_str())).cloned().collect();
+                callees.sort();
+                for &callee in &callees {
+                    graph.callers[callee].insert(id);
+                    graph.callsites[callee].push(bb);
+                }
+                graph.calls.insert(bb, callees);
+            }
+        }
+        graph
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.callers.is_empty()
+    }
+
+    // Computes the distances of the functions to the functions of the target
+    // BBs, returns the BBs calling a function whose distance changed
+    pub fn update(&mut self, target_bbs: &[BbId]) -> Vec<BbId> {
+        let mut targets: Vec<FuncId> = target_bbs.iter().filter_map(|bb| self.functions.get(bb)).cloned().collect();
+        targets.sort();
+        targets.dedup();
+
+        // Sums of the inverses, one breadth-first search up the callers per
+        // target function
+        let mut sums = vec![0.0; self.callers.len()];
+        for t in targets {
+            let mut calls: HashMap<FuncId, u32> = HashMap::new();
+            let mut worklist = VecDeque::new();
+            calls.insert(t, 0);
+            worklist.push_back(t);
+            while let Some(f) = worklist.pop_front() {
+                let d = calls[&f];
+                sums[f] += 1.0 / (1.0 + d as f64);
+                for &caller in &self.callers[f] {
+                    if !calls.contains_key(&caller) {
+                        calls.insert(caller, d + 1);
+                        worklist.push_back(caller);
+                    }
+                }
+            }
+        }
+
+        let mut changed = vec![];
+        for (f, sum) in sums.into_iter().enumerate() {
+            let distance = if sum > 0.0 { Some(1.0 / sum) } else { None };
+            if distance != self.distances[f] {
+                self.distances[f] = distance;
+                changed.extend(&self.callsites[f]);
+            }
+        }
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+
+    // Distance of a BB calling functions: CALL_FACTOR times the distance of
+    // its closest callee. None if the BB calls no function reaching a target.
+    pub fn callsite_distance(&self, bb: BbId) -> Option<Score> {
+        let distance = self.calls.get(&bb)?.iter()
+            .filter_map(|&f| self.distances[f])
+            .fold(None, |min: Option<f64>, d| Some(min.map_or(d, |m| m.min(d))))?;
+        // A call is never as close as a target
+        Some(((CALL_FACTOR * distance).round() as Score).max(1).min(UNDEF_SCORE - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, bbs: &[BbId], calls: &[(BbId, &str)]) -> FunctionInfo {
+        let mut info = FunctionInfo { name: name.to_string(), bbs: bbs.iter().cloned().collect(), ..Default::default() };
+        for &(bb, callee) in calls {
+            info.calls.entry(bb).or_insert_with(HashSet::new).insert(callee.to_string());
+        }
+        info
+    }
+
+    #[test]
+    fn callgraph_distance() {
+        // main calls parse and log, parse calls check and log
+        let infos = vec![
+            function("main", &[1, 2, 3], &[(1, "parse"), (2, "log")]),
+            function("parse", &[10, 11], &[(10, "check"), (11, "log"), (11, "strlen")]),
+            function("check", &[20], &[]),
+            function("log", &[30], &[]),
+        ];
+        let mut graph = CallGraph::new(&infos);
+
+        // Target in check
+        assert_eq!(graph.update(&[20]), vec![1, 10]);
+        assert_eq!(graph.callsite_distance(10), Some(10));
+        assert_eq!(graph.callsite_distance(1), Some(20));
+        assert_eq!(graph.callsite_distance(2), None);
+        assert_eq!(graph.callsite_distance(11), None);
+        assert_eq!(graph.callsite_distance(3), None);
+
+        // Targets in check and log, both one call away from parse: 1 / (1/2 + 1/2)
+        assert_eq!(graph.update(&[20, 30]), vec![1, 2, 11]);
+        assert_eq!(graph.callsite_distance(1), Some(10));
+        assert_eq!(graph.callsite_distance(11), Some(10));
+
+        assert_eq!(graph.update(&[]).len(), 4);
+        assert_eq!(graph.callsite_distance(1), None);
+    }
+}
@@ -1,12 +1,14 @@
-use std::f64;
-use math::mean;
 use petgraph::graphmap::DiGraphMap;
-use std::collections::{HashSet, HashMap};
-use std::time::Instant;
-use petgraph::visit::{Reversed, Bfs, Dfs};
+use std::collections::{HashSet, HashMap, VecDeque};
+use std::sync::Arc;
 use petgraph::{Incoming, Outgoing};
 use angora_common::tag::TagSeg;
-use super::fparse::CfgFile;
+use super::fparse::{CfgFile, TargetInfo};
+use super::metric::{DistanceMetric, HarmonicMean, Successor};
+use super::context::{Context, ContextScores};
+use super::callgraph::CallGraph;
+use super::state::CfgState;
+use super::export::{CfgExport, EdgeExport, NodeExport};
 
 pub type CmpId = u32;
 pub type BbId = u32;
@@ -15,9 +17,48 @@ pub type Edge = (BbId, BbId);
 pub type Score = u32;
 pub type FixedBytes = Vec<(usize, u8)>;
 
-const TARGET_SCORE: Score = 0;
-const UNDEF_SCORE: Score = std::u32::MAX;
+pub const TARGET_SCORE: Score = 0;
+pub const UNDEF_SCORE: Score = std::u32::MAX;
+// Distance added per unit of weight below the heaviest target, a target of
+// weight w is as far as a BB WEIGHT_DISTANCE * (max - w) away from the
+// heaviest targets
+const WEIGHT_DISTANCE: Score = 4;
+// Updates of a BB in one propagation after which it is checked for a cycle
+const CYCLE_CHECK_UPDATES: u32 = 32;
+
+// Waypoints of a code flow, targeted one after the other
+#[derive(Clone, Debug)]
+struct StagedFlow {
+    waypoints: Vec<HashSet<CmpId>>,
+    stage: usize,
+}
+
+impl StagedFlow {
+    fn current(&self) -> &HashSet<CmpId> {
+        &self.waypoints[self.stage]
+    }
 
+    // The last waypoint stays a target until it is solved
+    fn can_advance(&self, cmp: CmpId) -> bool {
+        self.stage + 1 < self.waypoints.len() && self.current().contains(&cmp)
+    }
+
+    // Removes a retired cmp from the waypoints. Returns true if the flow
+    // moved on because its current waypoint was left empty.
+    fn remove(&mut self, cmp: CmpId) -> bool {
+        let was_current = self.current().contains(&cmp);
+        for w in self.waypoints.iter_mut() {
+            w.remove(&cmp);
+        }
+        if !was_current {
+            return false;
+        }
+        while self.current().is_empty() && self.stage + 1 < self.waypoints.len() {
+            self.stage += 1;
+        }
+        !self.current().is_empty()
+    }
+}
 
 #[derive(Clone)]
 pub struct ControlFlowGraph {
@@ -31,6 +72,19 @@ pub struct ControlFlowGraph {
     callsite_dominators: HashMap<CallSiteId, HashSet<CmpId>>,
     dominator_cmps: HashSet<CmpId>,
     magic_bytes: HashMap<Edge, FixedBytes>,
+    flows: Vec<StagedFlow>,
+    // Intermediate waypoints of the code flows, no longer targeted once
+    // reached but not solved either
+    reached_waypoints: HashSet<CmpId>,
+    metric: Arc<dyn DistanceMetric>,
+    runtime_edges: HashSet<Edge>,
+    target_info: HashMap<CmpId, TargetInfo>,
+    // Targets whose info was changed at runtime, saved with the state
+    changed_target_info: HashSet<CmpId>,
+    max_weight: u32,
+    context_sensitive: bool,
+    context_scores: ContextScores,
+    call_graph: CallGraph,
 }
 
 
@@ -38,14 +92,27 @@ pub struct ControlFlowGraph {
 // A CFG of branches (CMPs)
 impl ControlFlowGraph {
     //pub fn new(targets: HashSet<CmpId>) -> ControlFlowGraph {
-    pub fn new(data: CfgFile) -> ControlFlowGraph {
+    pub fn new(data: CfgFile, metric: Arc<dyn DistanceMetric>) -> ControlFlowGraph {
         let mut dominator_cmps = HashSet::new();
         for s in data.callsite_dominators.values() {
             dominator_cmps.extend(s)
         }
+        let flows: Vec<StagedFlow> = data.flows.into_iter()
+            .filter(|w| !w.is_empty())
+            .map(|waypoints| StagedFlow { waypoints, stage: 0 })
+            .collect();
+        let mut targets = data.targets;
+        for f in &flows {
+            targets.extend(f.current());
+        }
+        let max_weight = data.target_info.values().map(|i| i.weight).max().unwrap_or(1).max(1);
+        let call_graph = CallGraph::new(&data.functions);
+        if metric.uses_call_graph() && call_graph.is_empty() {
+            warn!("The {} metric needs the call graph, which is not in the targets file", metric.name());
+        }
         let mut result = ControlFlowGraph {
             graph: DiGraphMap::new(),
-            targets: data.targets,
+            targets,
             id_mapping: data.id_mapping.clone(),
             reverse_id_mapping: Self::reverse_id_mapping(data.id_mapping),
             solved_targets: HashSet::new(),
@@ -54,6 +121,16 @@ impl ControlFlowGraph {
             callsite_dominators: data.callsite_dominators,
             dominator_cmps,
             magic_bytes: HashMap::new(),
+            flows,
+            reached_waypoints: HashSet::new(),
+            metric,
+            runtime_edges: HashSet::new(),
+            target_info: data.target_info,
+            changed_target_info: HashSet::new(),
+            max_weight,
+            context_sensitive: false,
+            context_scores: ContextScores::default(),
+            call_graph,
         };
 
         for e in data.edges {
@@ -63,6 +140,7 @@ impl ControlFlowGraph {
 
         info!("INIT CFG: dominators: {:?}", result.dominator_cmps);
         info!("INIT ID mapping: {:?}", result.id_mapping);
+        info!("INIT distance metric: {}", result.metric.name());
 
         result
     }
@@ -79,6 +157,16 @@ impl ControlFlowGraph {
             callsite_dominators: HashMap::new(),
             dominator_cmps: HashSet::new(),
             magic_bytes: HashMap::new(),
+            flows: vec![],
+            reached_waypoints: HashSet::new(),
+            metric: Arc::new(HarmonicMean),
+            runtime_edges: HashSet::new(),
+            target_info: HashMap::new(),
+            changed_target_info: HashSet::new(),
+            max_weight: 1,
+            context_sensitive: false,
+            context_scores: ContextScores::default(),
+            call_graph: CallGraph::default(),
         };
 
         result
@@ -87,6 +175,9 @@ impl ControlFlowGraph {
 
     pub fn add_edge(&mut self, edge: Edge) -> bool {
         let result = !self.has_edge(edge);
+        if result {
+            self.runtime_edges.insert(edge);
+        }
         self.handle_new_edge(edge);
         debug!("Added CFG edge {:?} {}", edge, self.targets.contains(&edge.1));
         result
@@ -107,12 +198,25 @@ impl ControlFlowGraph {
     }
 
     fn init_prop_targets(&mut self) {
-        // should be possible without cloning
-        for target in &self.targets.clone() {
-            if let Some(&bb) = self.get_bb_from_cmp(&target) {
-                self.propagate_score(bb);
-            }
+        let start = self.targets.iter()
+            .filter_map(|target| self.get_bb_from_cmp(target))
+            .cloned()
+            .collect();
+        self.propagate_targets(start);
+    }
+
+    // Propagates the scores after the targets changed, starting from the
+    // BBs of the changed targets and, when the metric uses the call graph,
+    // from the BBs calling the functions whose distance changed
+    fn propagate_targets(&mut self, mut start: Vec<BbId>) {
+        if self.metric.uses_call_graph() {
+            let target_bbs: Vec<BbId> = self.targets.iter()
+                .filter_map(|target| self.get_bb_from_cmp(target))
+                .cloned()
+                .collect();
+            start.extend(self.call_graph.update(&target_bbs));
         }
+        self.propagate_scores(start);
     }
 
     pub fn set_edge_indirect(&mut self, edge: Edge, callsite: CallSiteId) {
@@ -159,7 +263,7 @@ impl ControlFlowGraph {
     pub fn remove_target(&mut self, cmp: CmpId) {
         if self.targets.remove(&cmp) {
             if let Some(&bb) = self.get_bb_from_cmp(&cmp) {
-                self.propagate_score(bb);
+                self.propagate_targets(vec![bb]);
             }
             else {
                 warn!("CFG warning: couldn't propagate score when removing target");
@@ -168,10 +272,136 @@ impl ControlFlowGraph {
         }
     }
 
+    // Returns false if the cmp is already a target or was solved
+    pub fn add_target(&mut self, cmp: CmpId) -> bool {
+        if self.solved_targets.contains(&cmp) || !self.targets.insert(cmp) {
+            return false;
+        }
+        if let Some(&bb) = self.get_bb_from_cmp(&cmp) {
+            self.propagate_targets(vec![bb]);
+        }
+        else {
+            warn!("CFG warning: couldn't propagate score when adding target");
+        }
+        true
+    }
+
+    // Stops targeting a cmp without considering it solved, also as a
+    // waypoint of the code flows. Returns false if the cmp is neither a
+    // target nor a waypoint.
+    pub fn retire_target(&mut self, cmp: CmpId) -> bool {
+        let mut in_flows = false;
+        let mut next = HashSet::new();
+        for f in self.flows.iter_mut() {
+            in_flows |= f.waypoints.iter().any(|w| w.contains(&cmp));
+            if f.remove(cmp) {
+                next.extend(f.current());
+            }
+        }
+        for c in next {
+            self.add_target(c);
+        }
+        self._retire_target(cmp) || in_flows
+    }
+
+    fn _retire_target(&mut self, cmp: CmpId) -> bool {
+        if !self.targets.remove(&cmp) {
+            return false;
+        }
+        if let Some(&bb) = self.get_bb_from_cmp(&cmp) {
+            self.propagate_targets(vec![bb]);
+        }
+        else {
+            warn!("CFG warning: couldn't propagate score when retiring target");
+        }
+        true
+    }
+
+    // Changes the weight and label of a target. The distances of every
+    // target depend on the heaviest one, so they are all propagated again.
+    pub fn set_target_info(&mut self, cmp: CmpId, weight: Option<u32>, label: Option<String>) {
+        let info = self.target_info.entry(cmp).or_insert(TargetInfo { weight: 1, label: None });
+        if let Some(w) = weight {
+            info.weight = w;
+        }
+        if label.is_some() {
+            info.label = label;
+        }
+        self.changed_target_info.insert(cmp);
+        if weight.is_some() {
+            self.update_weights();
+        }
+    }
+
+    fn update_weights(&mut self) {
+        self.max_weight = self.target_info.values().map(|i| i.weight).max().unwrap_or(1).max(1);
+        self.init_prop_targets();
+    }
+
+    pub fn is_waypoint(&self, cmp: CmpId) -> bool {
+        self.flows.iter().any(|f| f.can_advance(cmp))
+    }
+
+    // Moves the code flows whose current waypoint was reached to their next
+    // waypoint. Returns true if a flow advanced.
+    pub fn reach_waypoint(&mut self, cmp: CmpId) -> bool {
+        let mut reached = HashSet::new();
+        let mut next = HashSet::new();
+        for f in self.flows.iter_mut() {
+            if f.can_advance(cmp) {
+                reached.extend(f.current());
+                f.stage += 1;
+                next.extend(f.current());
+            }
+        }
+
+        for &c in &next {
+            self.add_target(c);
+        }
+        for c in reached {
+            if !next.contains(&c) && self._retire_target(c) {
+                self.reached_waypoints.insert(c);
+            }
+        }
+        !next.is_empty()
+    }
+
     pub fn is_target(&self, cmp: CmpId) -> bool {
         self.targets.contains(&cmp) || self.solved_targets.contains(&cmp)
     }
 
+    pub fn target_weight(&self, cmp: CmpId) -> u32 {
+        self.target_info.get(&cmp).map_or(1, |i| i.weight)
+    }
+
+    pub fn target_label(&self, cmp: CmpId) -> Option<&str> {
+        self.target_info.get(&cmp).and_then(|i| i.label.as_deref())
+    }
+
+    // Lighter targets start farther away so that the paths to the heavier
+    // ones are preferred by every metric
+    fn target_score(&self, cmp: CmpId) -> Score {
+        TARGET_SCORE + self.max_weight.saturating_sub(self.target_weight(cmp)) * WEIGHT_DISTANCE
+    }
+
+    pub fn is_solved(&self, cmp: CmpId) -> bool {
+        self.solved_targets.contains(&cmp)
+    }
+
+    pub fn is_reached_waypoint(&self, cmp: CmpId) -> bool {
+        self.reached_waypoints.contains(&cmp)
+    }
+
+    // Targets still fuzzed along with the solved ones and the reached
+    // waypoints
+    pub fn get_targets(&self) -> HashSet<CmpId> {
+        self.targets.iter()
+            .chain(self.solved_targets.iter())
+            .chain(self.reached_waypoints.iter())
+            .cloned()
+            .collect()
+    }
+
     pub fn get_bb_from_cmp(&self, cmp: &CmpId) -> Option<&BbId> {
         return self.reverse_id_mapping.get(cmp);
     }
@@ -201,34 +431,108 @@ impl ControlFlowGraph {
 
 
     fn propagate_score(&mut self, bb: BbId) {
-        // Check how long it takes to clone the full graph. Result: On my machine the cloning part of adding all the edges to the graph at the beginning takes around 25% of the total time. Definitely expensive but probably not worth to change it right now.
-        #[cfg(test)]
-        let now = Instant::now();
-        let graph_copy = &self.graph.clone();
-        #[cfg(test)]
-        println!("time to copy with node size {:?} and edge size {:?}: {}", &self.graph.node_count(), &self.graph.edge_count(), now.elapsed().as_micros());
-        #[cfg(test)]
-        let now2 = Instant::now();
-        let rev_graph = Reversed(graph_copy);
-        let mut visitor = Bfs::new(rev_graph, bb);
-
-        while let Some(visited) = visitor.next(rev_graph) {
-            let new_score = self._score_for_bb(visited);
-            let mut predecessors = vec![];
-            {
-                let neighbors = self.graph.neighbors_directed(visited, Incoming);
-                for n in neighbors {
-                    predecessors.push(n);
+        self.propagate_scores(vec![bb]);
+    }
+
+    // Propagates the scores of the start BBs to their predecessors. A BB is
+    // rescored when the score of a successor changed and its predecessors
+    // are queued only if its own score changed, so the propagation stops
+    // where the scores stay the same. The scores of a cycle may keep growing
+    // (or diverge, e.g. the sums of the coverage metric): every
+    // CYCLE_CHECK_UPDATES updates of a BB, the updated BBs on a cycle through
+    // it restart from undefined and from then on only take lower scores.
+    fn propagate_scores(&mut self, start: Vec<BbId>) {
+        let mut worklist = VecDeque::new();
+        let mut queued = HashSet::new();
+        let mut updates: HashMap<BbId, u32> = HashMap::new();
+        let mut lowering = HashSet::new();
+
+        // The start BBs changed, whatever the score kept on their edges
+        for bb in start {
+            if !self.graph.contains_node(bb) {
+                continue;
+            }
+            let score = self._score_for_bb(bb);
+            self.set_score(bb, score);
+            for p in self.graph.neighbors_directed(bb, Incoming) {
+                if queued.insert(p) {
+                    worklist.push_back(p);
+                }
+            }
+        }
+
+        while let Some(bb) = worklist.pop_front() {
+            queued.remove(&bb);
+            let score = self._score_for_bb(bb);
+            let stored = self.stored_score(bb);
+            if score == stored || (score > stored && lowering.contains(&bb)) {
+                continue;
+            }
+            self.set_score(bb, score);
+            let mut changed = vec![bb];
+
+            let count = updates.entry(bb).or_insert(0);
+            *count += 1;
+            if *count % CYCLE_CHECK_UPDATES == 0 && !lowering.contains(&bb) {
+                for c in self.updated_cycle(bb, &updates) {
+                    if lowering.insert(c) {
+                        self.set_score(c, UNDEF_SCORE);
+                        changed.push(c);
+                    }
                 }
             }
-            for p in predecessors {
-                self.graph.add_edge(p, visited, new_score);
+
+            // The BBs restarting from undefined are rescored too
+            for (i, c) in changed.into_iter().enumerate() {
+                if i > 0 && queued.insert(c) {
+                    worklist.push_back(c);
+                }
+                for p in self.graph.neighbors_directed(c, Incoming) {
+                    if queued.insert(p) {
+                        worklist.push_back(p);
+                    }
+                }
             }
         }
-        #[cfg(test)]
-        println!("time for rest of propagate: {}", now2.elapsed().as_micros());
     }
-    
+
+    // BBs updated by the propagation which are on a cycle through `bb`,
+    // empty if there is none
+    fn updated_cycle(&self, bb: BbId, updates: &HashMap<BbId, u32>) -> Vec<BbId> {
+        let reachable = |dir| {
+            let mut seen = HashSet::new();
+            let mut stack = vec![bb];
+            while let Some(n) = stack.pop() {
+                for m in self.graph.neighbors_directed(n, dir) {
+                    if updates.contains_key(&m) && seen.insert(m) {
+                        stack.push(m);
+                    }
+                }
+            }
+            seen
+        };
+        let successors = reachable(Outgoing);
+        let mut cycle: Vec<BbId> = reachable(Incoming).into_iter()
+            .filter(|n| successors.contains(n))
+            .collect();
+        cycle.sort();
+        cycle
+    }
+
+    // The score of a BB is kept on its incoming edges
+    fn set_score(&mut self, bb: BbId, score: Score) {
+        let predecessors: Vec<BbId> = self.graph.neighbors_directed(bb, Incoming).collect();
+        for p in predecessors {
+            self.graph.add_edge(p, bb, score);
+        }
+    }
+
+    fn stored_score(&self, bb: BbId) -> Score {
+        self.graph.neighbors_directed(bb, Incoming).next()
+            .and_then(|p| self.graph.edge_weight(p, bb))
+            .cloned()
+            .unwrap_or(UNDEF_SCORE)
+    }
 
     pub fn has_edge(&self, edge: Edge) -> bool {
         let (a,b) = edge;
@@ -252,42 +556,142 @@ impl ControlFlowGraph {
         return rev_mapping;
     }
 
-    fn aggregate_score(ovals: Vec<Score>) -> Score {
-        //Self::score_greedy(ovals)
-        //Self::score_coverage(ovals)
-        Self::score_harmonic_mean(ovals)
+    pub fn get_state(&self) -> CfgState {
+        let mut state = CfgState {
+            edges: self.runtime_edges.iter().cloned().collect(),
+            callsite_edges: self.callsite_edges.iter()
+                .map(|(&cs, edges)| (cs, edges.iter().cloned().collect()))
+                .collect(),
+            magic_bytes: self.magic_bytes.iter().map(|(&e, b)| (e, b.clone())).collect(),
+            solved_targets: self.solved_targets.iter().cloned().collect(),
+            flow_stages: self.flows.iter().map(|f| f.stage).collect(),
+            reached_waypoints: self.reached_waypoints.iter().cloned().collect(),
+            target_info: self.changed_target_info.iter()
+                .filter_map(|c| self.target_info.get(c).map(|i| (*c, i.clone())))
+                .collect(),
+            contexts: self.context_scores.entries(),
+            targets_log: Default::default(),
+        };
+        // Keep the file stable between dumps
+        state.edges.sort();
+        state.callsite_edges.sort();
+        for (_, edges) in state.callsite_edges.iter_mut() {
+            edges.sort();
+        }
+        state.magic_bytes.sort();
+        state.solved_targets.sort();
+        state.reached_waypoints.sort();
+        state.target_info.sort_by_key(|&(c, _)| c);
+        state
     }
 
-    fn score_harmonic_mean(ovals: Vec<Score>) -> Score {
-        if ovals.len() == 0 {
-            return UNDEF_SCORE;
+    // Merges the state saved by a previous run. The targets solved by the
+    // previous run which are still in the targets file stay solved, and the
+    // code flows are matched by their position in the targets file.
+    pub fn merge_state(&mut self, state: CfgState) {
+        for (cs, edges) in state.callsite_edges {
+            for e in edges {
+                self.set_edge_indirect(e, cs);
+            }
         }
-        let vals = ovals.into_iter().filter(|v| *v != UNDEF_SCORE);
-        let fvals : Vec<f64> = vals.into_iter().map(|x| x as f64).collect();
-        if fvals.is_empty() {
-            return UNDEF_SCORE;
+        self.magic_bytes.extend(state.magic_bytes);
+        for e in state.edges {
+            if !self.has_edge(e) {
+                self.add_edge(e);
+            }
+        }
+        for cmp in state.solved_targets {
+            self.remove_target(cmp);
         }
-        return mean::harmonic(fvals.as_slice()) as u32;
+
+        for (flow, &stage) in self.flows.iter_mut().zip(state.flow_stages.iter()) {
+            if stage < flow.waypoints.len() {
+                flow.stage = stage;
+            }
+        }
+        let current: HashSet<CmpId> = self.flows.iter().flat_map(|f| f.current().iter().cloned()).collect();
+        for &c in &current {
+            self.add_target(c);
+        }
+        for c in state.reached_waypoints {
+            if !current.contains(&c) && self._retire_target(c) {
+                self.reached_waypoints.insert(c);
+            }
+        }
+
+        if !state.target_info.is_empty() {
+            for (cmp, info) in state.target_info {
+                self.target_info.insert(cmp, info);
+                self.changed_target_info.insert(cmp);
+            }
+            self.update_weights();
+        }
+        self.context_scores.merge(state.contexts);
+        info!("Merged CFG state: {} runtime edges, {} solved targets, {} reached waypoints",
+              self.runtime_edges.len(), self.solved_targets.len(), self.reached_waypoints.len());
     }
 
-    #[allow(dead_code)]
-    fn score_greedy(ovals: Vec<Score>) -> Score {
-        let vals = ovals.into_iter().filter(|v| *v != UNDEF_SCORE);
-        if let Some(v) = vals.min() {
-            v + 1
-        } else {
-            UNDEF_SCORE
+    // `hits` counts the tracked inputs hitting each cmp
+    pub fn export(&self, hits: &HashMap<CmpId, usize>) -> CfgExport {
+        let score = |s: Score| if s == UNDEF_SCORE { None } else { Some(s) };
+        let mut callsites = HashMap::new();
+        for (&cs, edges) in &self.callsite_edges {
+            for &e in edges {
+                callsites.insert(e, cs);
+            }
         }
+
+        let mut nodes: Vec<NodeExport> = self.graph.nodes().map(|bb| {
+            let mut cmpids: Vec<CmpId> = self.id_mapping.get(&bb)
+                .map(|cmps| cmps.iter().cloned().collect())
+                .unwrap_or_default();
+            cmpids.sort();
+            NodeExport {
+                bbid: bb,
+                score: score(self._score_for_bb(bb)),
+                target: cmpids.iter().any(|c| self.targets.contains(c)),
+                solved: cmpids.iter().any(|c| self.solved_targets.contains(c)),
+                hits: cmpids.iter().filter_map(|c| hits.get(c)).cloned().max().unwrap_or(0),
+                cmpids,
+            }
+        }).collect();
+        nodes.sort_by_key(|n| n.bbid);
+
+        let mut edges: Vec<EdgeExport> = self.graph.all_edges().map(|(src, dst, &s)| EdgeExport {
+            src,
+            dst,
+            score: score(s),
+            callsite: callsites.get(&(src, dst)).cloned(),
+        }).collect();
+        edges.sort_by_key(|e| (e.src, e.dst));
+
+        CfgExport { metric: self.metric_name().to_string(), nodes, edges }
     }
 
-    #[allow(dead_code)]
-    fn score_coverage(ovals: Vec<Score>) -> Score {
-        if ovals.len() == 0 {
-            return UNDEF_SCORE;
+    // Number of edges from the closest start BB to every BB reachable from
+    // the start BBs
+    pub fn hop_distances(&self, start: Vec<BbId>) -> HashMap<BbId, u32> {
+        let mut distances: HashMap<BbId, u32> = HashMap::new();
+        let mut worklist = VecDeque::new();
+        for bb in start {
+            if self.graph.contains_node(bb) && distances.insert(bb, 0).is_none() {
+                worklist.push_back(bb);
+            }
+        }
+        while let Some(bb) = worklist.pop_front() {
+            let d = distances[&bb] + 1;
+            for succ in self.graph.neighbors_directed(bb, Outgoing) {
+                if !distances.contains_key(&succ) {
+                    distances.insert(succ, d);
+                    worklist.push_back(succ);
+                }
+            }
         }
-        let vals = ovals.into_iter().filter(|v| *v != UNDEF_SCORE);
-        let vals_norm = vals.into_iter().map(|v| if v == TARGET_SCORE {1} else {v});
-        vals_norm.sum()
+        distances
+    }
+
+    pub fn metric_name(&self) -> &'static str {
+        self.metric.name()
     }
 
     fn has_path_to_target_bb(&self, start: BbId) -> bool {
@@ -322,6 +726,42 @@ impl ControlFlowGraph {
         score
     }
 
+    pub fn set_context_sensitive(&mut self, enabled: bool) {
+        self.context_sensitive = enabled;
+    }
+
+    pub fn is_context_sensitive(&self) -> bool {
+        self.context_sensitive
+    }
+
+    // Learns the continuations of the calling contexts from the conds of a
+    // tracked input, in execution order
+    pub fn learn_contexts(&mut self, trace: &[(CmpId, Context)]) {
+        if !self.context_sensitive {
+            return;
+        }
+        let scored: Vec<(CmpId, Context, Score)> = trace.iter()
+            .map(|&(cmp, ctx)| {
+                let score = self.get_bb_from_cmp(&cmp).map_or(UNDEF_SCORE, |&bb| self._score_for_bb(bb));
+                (cmp, ctx, score)
+            })
+            .collect();
+        self.context_scores.learn(&scored);
+    }
+
+    // Distance of a cond under its calling context, the distance of its BB
+    // unless context sensitivity is enabled
+    pub fn score_for_context(&self, bb: BbId, cmp: CmpId, ctx: Context, inp: Vec<u8>) -> Score {
+        let score = self.score_for_bb_inp(bb, inp);
+        if !self.context_sensitive || score == UNDEF_SCORE {
+            return score;
+        }
+        match self.context_scores.penalty(cmp, ctx) {
+            UNDEF_SCORE => UNDEF_SCORE,
+            penalty => score.saturating_add(penalty).min(UNDEF_SCORE - 1),
+        }
+    }
+
     fn _score_for_bb(&self, bb: BbId) -> Score {
         self._score_for_bb_inp(bb, vec![])
     }
@@ -333,11 +773,18 @@ impl ControlFlowGraph {
         if let Some(cmp_set) = &self.id_mapping.get(&bb) {
             has_cmp = true;
             num_cmps = cmp_set.len() as u32;
-            for cmp in *cmp_set {
-                if self.targets.contains(&cmp) {
-                    debug!("Calculate score for target: {}", cmp);
-                    return TARGET_SCORE;
-                }
+            let target_score = cmp_set.iter()
+                .filter(|cmp| self.targets.contains(cmp))
+                .map(|&cmp| self.target_score(cmp))
+                .min();
+            if let Some(score) = target_score {
+                debug!("Calculate score for target in bb: {}", bb);
+                return score;
+            }
+        }
+        if self.metric.uses_call_graph() {
+            if let Some(score) = self.call_graph.callsite_distance(bb) {
+                return score;
             }
         }
         let mut neighbors = self.graph.neighbors_directed(bb, Outgoing);
@@ -351,10 +798,10 @@ impl ControlFlowGraph {
             }
             debug!("Counting edge: {:?}", edge);
             if let Some(s) = self.graph.edge_weight(bb, n) {
-                scores.push(*s);
+                scores.push(Successor { score: *s, is_call: self.indirect_edges.contains(&edge) });
             }
         }
-        let aggregate = Self::aggregate_score(scores.clone());
+        let aggregate = self.metric.aggregate(&scores);
         // increase distance by number of cmpids when passing by.
         if has_cmp && aggregate != UNDEF_SCORE {aggregate+num_cmps} else {aggregate}
     }
@@ -390,6 +837,8 @@ mod tests {
     use crate::itertools::Itertools;
     use rand::thread_rng;
     use rand::seq::SliceRandom;
+    use crate::dyncfg::metric::{Aflgo, Coverage, Greedy};
+    use crate::dyncfg::fparse::FunctionInfo;
 
     fn test_new(targets: HashSet<CmpId>, id_mapping: HashMap<BbId, HashSet<CmpId>>) -> ControlFlowGraph {
         let result = ControlFlowGraph {
@@ -403,6 +852,16 @@ mod tests {
             callsite_dominators: HashMap::new(),
             dominator_cmps: HashSet::new(),
             magic_bytes: HashMap::new(),
+            flows: vec![],
+            reached_waypoints: HashSet::new(),
+            metric: Arc::new(HarmonicMean),
+            runtime_edges: HashSet::new(),
+            target_info: HashMap::new(),
+            changed_target_info: HashSet::new(),
+            max_weight: 1,
+            context_sensitive: false,
+            context_scores: ContextScores::default(),
+            call_graph: CallGraph::default(),
         };
 
         result
@@ -463,31 +922,156 @@ mod tests {
         }
     }
 
-    // Test whether or not the graph clone in propagate_score is too time consuming.
+    // Test whether the incremental propagation gives the same scores as
+    // propagating the targets on the full graph. Timings are in benches/.
     #[test]
-    fn cfg_clone_time() {
-        // Create CFG
-        let num_nodes = 1000000;
-        let target_vec = vec![num_nodes*10];
-        let targets: HashSet<CmpId> = HashSet::from_iter(target_vec.iter().cloned());
-        let id_mapping: HashMap<BbId, HashSet<CmpId>> = [(num_nodes-1, vec![num_nodes*10].into_iter().collect())].iter().cloned().collect();
+    fn cfg_incremental_propagation() {
+        // Chain of BBs, each one with a cmp, the last one is the target
+        let num_nodes = 1000;
+        let target = num_nodes*10;
+        let targets: HashSet<CmpId> = vec![target].into_iter().collect();
+        let mut id_mapping: HashMap<BbId, HashSet<CmpId>> = (0..num_nodes-1).map(|bb| (bb, vec![bb+num_nodes].into_iter().collect())).collect();
+        id_mapping.insert(num_nodes-1, targets.clone());
 
-        let mut cfg = test_new(targets, id_mapping);
         let nodes: Vec<BbId> = (0..num_nodes).collect();
-        
         let mut edges = vec![];
         for (a,b) in nodes.clone().into_iter().tuple_windows() {
             edges.push((a,b));
         }
-
         edges.shuffle(&mut thread_rng());
-        
-        let now = Instant::now();
+
+        // Edges found at runtime. Greedy distances are exact, the harmonic
+        // mean of a single value may be rounded down.
+        let mut cfg = test_new(targets.clone(), id_mapping.clone());
+        cfg.metric = Arc::new(Greedy);
         for e in edges.clone() {
-            cfg.init_add_edge(e);
+            cfg.add_edge(e);
+        }
+
+        // Edges known at startup
+        let mut full = test_new(targets, id_mapping);
+        full.metric = Arc::new(Greedy);
+        for e in edges.clone() {
+            full.init_add_edge(e);
+        }
+        full.init_prop_targets();
+
+        for bb in nodes.clone() {
+            assert_eq!(cfg.score_for_bb(bb), 2*(num_nodes-1-bb));
+            assert_eq!(cfg.score_for_bb(bb), full.score_for_bb(bb));
+        }
+
+        cfg.remove_target(target);
+        for bb in nodes {
+            assert_eq!(cfg.has_score(bb), false);
+        }
+    }
+
+    // Test whether a BB with many successors at different depths gets the
+    // aggregate of their final scores, whatever the order of the edges
+    #[test]
+    fn cfg_converging_successors() {
+        // BB 0 branches to 20 chains of length 1 to 20 leading to the target
+        // BB 5000, every BB has a cmp
+        let num_succs = 20;
+        let target = 5000;
+        let mut edges = vec![];
+        for i in 1..=num_succs {
+            let chain: Vec<BbId> = (0..i).map(|k| i * 100 + k).collect();
+            edges.push((0, chain[0]));
+            for (a, b) in chain.iter().cloned().tuple_windows() {
+                edges.push((a, b));
+            }
+            edges.push((chain[chain.len() - 1], target));
+        }
+        let mut bbs: HashSet<BbId> = edges.iter().flat_map(|&(a, b)| vec![a, b]).collect();
+        bbs.remove(&target);
+        let mut id_mapping: HashMap<BbId, HashSet<CmpId>> = bbs.into_iter().map(|bb| (bb, vec![bb + 10000].into_iter().collect())).collect();
+        id_mapping.insert(target, vec![target + 10000].into_iter().collect());
+        let targets: HashSet<CmpId> = vec![target + 10000].into_iter().collect();
+
+        let succs: Vec<Successor> = (1..=num_succs).map(|i| Successor { score: i, is_call: false }).collect();
+        let expected = HarmonicMean.aggregate(&succs) + 1;
+
+        let mut full = test_new(targets.clone(), id_mapping.clone());
+        for &e in &edges {
+            full.init_add_edge(e);
+        }
+        full.init_prop_targets();
+        assert_eq!(full.score_for_bb(0), expected);
+
+        edges.shuffle(&mut thread_rng());
+        let mut cfg = test_new(targets, id_mapping);
+        for &e in &edges {
+            cfg.add_edge(e);
+        }
+        for i in 1..=num_succs {
+            assert_eq!(cfg.score_for_bb(i * 100), i);
+        }
+        assert_eq!(cfg.score_for_bb(0), expected);
+    }
+
+    // Test whether the propagation ends on cycles, even when the scores
+    // diverge, and whether the cycles lose their score with the target
+    #[test]
+    fn cfg_cycle_propagation() {
+        let id_mapping: HashMap<BbId, HashSet<CmpId>> = [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (30, vec![1200].into_iter().collect())].iter().cloned().collect();
+        let targets: HashSet<CmpId> = vec![1200].into_iter().collect();
+        let edges = vec![(0,10), (10,20), (20,10), (20,30)];
+        for metric in vec![Arc::new(Greedy) as Arc<dyn DistanceMetric>, Arc::new(HarmonicMean), Arc::new(Coverage)] {
+            let mut cfg = test_new(targets.clone(), id_mapping.clone());
+            cfg.metric = metric;
+            for &e in &edges {
+                cfg.add_edge(e);
+            }
+            for bb in vec![0, 10, 20] {
+                assert_eq!(cfg.has_score(bb), true);
+            }
+            if cfg.metric.name() == "greedy" {
+                assert_eq!(cfg.score_for_bb(20), 2);
+                assert_eq!(cfg.score_for_bb(10), 4);
+                assert_eq!(cfg.score_for_bb(0), 5);
+            }
+
+            cfg.remove_target(1200);
+            for bb in vec![0, 10, 20] {
+                assert_eq!(cfg.has_score(bb), false);
+            }
+        }
+    }
+
+    // Test whether a cycle losing its closest target gets the distance to the
+    // farther one, even when the cycle has to be restarted from undefined
+    #[test]
+    fn cfg_cycle_farther_target() {
+        // Cycle 10 <-> 20 next to the target BB 30, the target BB 1099 is at
+        // the end of a chain of 100 BBs starting at BB 1000
+        let mut edges = vec![(0,10), (10,20), (20,10), (20,30), (10,1000)];
+        edges.extend((1000..1099).map(|bb| (bb, bb + 1)));
+        let id_mapping: HashMap<BbId, HashSet<CmpId>> = edges.iter()
+            .flat_map(|&(a, b)| vec![a, b])
+            .filter(|&bb| bb != 0)
+            .map(|bb| (bb, vec![bb + 10000].into_iter().collect()))
+            .collect();
+
+        let mut cfg = test_new(vec![10030, 11099].into_iter().collect(), id_mapping.clone());
+        cfg.metric = Arc::new(Greedy);
+        for &e in &edges {
+            cfg.add_edge(e);
+        }
+        assert_eq!(cfg.score_for_bb(10), 4);
+        cfg.remove_target(10030);
+
+        let mut full = test_new(vec![11099].into_iter().collect(), id_mapping);
+        full.metric = Arc::new(Greedy);
+        for &e in &edges {
+            full.init_add_edge(e);
+        }
+        full.init_prop_targets();
+        assert_eq!(full.score_for_bb(10), 2 * 100);
+        for bb in vec![0, 10, 20, 30, 1000] {
+            assert_eq!(cfg.score_for_bb(bb), full.score_for_bb(bb));
         }
-        cfg.init_prop_targets();
-        println!("total time: {}", now.elapsed().as_micros());
     }
 
     // Test whether or not has_path_to_target_bb works
@@ -515,5 +1099,226 @@ mod tests {
         assert_eq!(cfg.has_path_to_target_bb(30), true);
         assert_eq!(cfg.has_path_to_target_bb(140), true);
     }
-}
 
+    // Test whether code flows are targeted one waypoint after the other
+    #[test]
+    fn cfg_staged_flows() {
+        let id_mapping: HashMap<BbId, HashSet<CmpId>> = [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (30, vec![1200].into_iter().collect()), (40, vec![1300].into_iter().collect())].iter().cloned().collect();
+        let flow: Vec<HashSet<CmpId>> = vec![vec![1100].into_iter().collect(), vec![1300].into_iter().collect()];
+        let data = CfgFile {
+            edges: vec![(0,10), (10,20), (10,30), (30,40)].into_iter().collect(),
+            id_mapping,
+            flows: vec![flow],
+            ..Default::default()
+        };
+        let mut cfg = ControlFlowGraph::new(data, Arc::new(HarmonicMean));
+
+        // Only the first waypoint is targeted
+        assert_eq!(cfg.has_path_to_target_bb(20), true);
+        assert_eq!(cfg.has_path_to_target_bb(30), false);
+        assert_eq!(cfg.is_waypoint(1100), true);
+        assert_eq!(cfg.is_waypoint(1300), false);
+
+        assert_eq!(cfg.reach_waypoint(1100), true);
+        assert_eq!(cfg.has_path_to_target_bb(20), false);
+        assert_eq!(cfg.has_path_to_target_bb(30), true);
+        // A reached waypoint is neither a target nor solved
+        assert_eq!(cfg.is_target(1100), false);
+        assert_eq!(cfg.is_solved(1100), false);
+        assert_eq!(cfg.is_reached_waypoint(1100), true);
+        assert_eq!(cfg.get_targets().contains(&1100), true);
+
+        // The last waypoint is a regular target
+        assert_eq!(cfg.reach_waypoint(1300), false);
+        assert_eq!(cfg.has_path_to_target_bb(40), true);
+    }
+
+    // Test whether retiring a waypoint moves its code flow on
+    #[test]
+    fn cfg_retire_waypoint() {
+        let id_mapping: HashMap<BbId, HashSet<CmpId>> = [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (30, vec![1200].into_iter().collect()), (40, vec![1300].into_iter().collect())].iter().cloned().collect();
+        let flow: Vec<HashSet<CmpId>> = vec![vec![1100].into_iter().collect(), vec![1200].into_iter().collect(), vec![1300].into_iter().collect()];
+        let data = CfgFile {
+            edges: vec![(0,10), (10,20), (10,30), (30,40)].into_iter().collect(),
+            id_mapping,
+            flows: vec![flow],
+            ..Default::default()
+        };
+        let mut cfg = ControlFlowGraph::new(data, Arc::new(HarmonicMean));
+
+        // A later waypoint is skipped, the current one moves the flow on
+        assert_eq!(cfg.retire_target(1200), true);
+        assert_eq!(cfg.retire_target(1100), true);
+        assert_eq!(cfg.is_target(1100), false);
+        assert_eq!(cfg.is_reached_waypoint(1100), false);
+        assert_eq!(cfg.is_target(1300), true);
+        assert_eq!(cfg.is_waypoint(1300), false);
+        assert_eq!(cfg.has_path_to_target_bb(20), false);
+        assert_eq!(cfg.has_path_to_target_bb(30), true);
+
+        assert_eq!(cfg.retire_target(1300), true);
+        assert_eq!(cfg.get_targets().is_empty(), true);
+        assert_eq!(cfg.retire_target(1300), false);
+    }
+
+    // Test whether the state saved by a run is merged back on resume
+    #[test]
+    fn cfg_state_resume() {
+        let new_cfg = || {
+            let id_mapping: HashMap<BbId, HashSet<CmpId>> = [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (40, vec![1300].into_iter().collect())].iter().cloned().collect();
+            let data = CfgFile {
+                targets: vec![1100, 1300].into_iter().collect(),
+                edges: vec![(0,10), (10,20), (30,40)].into_iter().collect(),
+                id_mapping,
+                ..Default::default()
+            };
+            ControlFlowGraph::new(data, Arc::new(HarmonicMean))
+        };
+
+        let mut cfg = new_cfg();
+        assert_eq!(cfg.has_path_to_target_bb(30), true);
+        cfg.add_edge((10,30));
+        cfg.set_edge_indirect((10,30), 10);
+        cfg.set_magic_bytes((1000,1300), &vec![7, 8, 9], &vec![TagSeg { sign: false, begin: 1, end: 2 }]);
+        cfg.remove_target(1100);
+        let state = cfg.get_state();
+        assert_eq!(state.edges, vec![(10,30)]);
+        assert_eq!(state.solved_targets, vec![1100]);
+
+        let mut resumed = new_cfg();
+        resumed.merge_state(state);
+        assert_eq!(resumed.get_state(), cfg.get_state());
+        assert_eq!(resumed.get_magic_bytes((1000,1300)), vec![(1, 8)]);
+        assert_eq!(resumed.is_solved(1100), true);
+        assert_eq!(resumed.has_path_to_target_bb(0), true);
+        assert_eq!(resumed.score_for_bb(0), cfg.score_for_bb(0));
+    }
+
+    // Test whether the code flows, the weights changed at runtime and the
+    // learned contexts are restored on resume
+    #[test]
+    fn cfg_state_resume_flows() {
+        let new_cfg = || {
+            let id_mapping: HashMap<BbId, HashSet<CmpId>> = [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (30, vec![1200].into_iter().collect()), (40, vec![1300].into_iter().collect())].iter().cloned().collect();
+            let flow: Vec<HashSet<CmpId>> = vec![vec![1100].into_iter().collect(), vec![1200].into_iter().collect(), vec![1300].into_iter().collect()];
+            let data = CfgFile {
+                targets: vec![1000].into_iter().collect(),
+                edges: vec![(0,10), (10,20), (10,30), (30,40)].into_iter().collect(),
+                id_mapping,
+                flows: vec![flow],
+                ..Default::default()
+            };
+            let mut cfg = ControlFlowGraph::new(data, Arc::new(HarmonicMean));
+            cfg.set_context_sensitive(true);
+            cfg
+        };
+
+        let mut cfg = new_cfg();
+        assert_eq!(cfg.reach_waypoint(1100), true);
+        cfg.set_target_info(1200, Some(3), None);
+        cfg.learn_contexts(&[(1000, 0), (1100, 1), (1200, 0)]);
+        cfg.learn_contexts(&[(1000, 0), (1100, 2)]);
+        let state = cfg.get_state();
+        assert_eq!(state.flow_stages, vec![1]);
+        assert_eq!(state.reached_waypoints, vec![1100]);
+
+        let mut resumed = new_cfg();
+        resumed.merge_state(state);
+        assert_eq!(resumed.get_state(), cfg.get_state());
+        assert_eq!(resumed.is_target(1100), false);
+        assert_eq!(resumed.is_reached_waypoint(1100), true);
+        assert_eq!(resumed.is_target(1200), true);
+        assert_eq!(resumed.is_waypoint(1200), true);
+        assert_eq!(resumed.target_weight(1200), 3);
+        assert_eq!(resumed.score_for_bb(0), cfg.score_for_bb(0));
+        assert_eq!(resumed.score_for_context(20, 1100, 2, vec![]), cfg.score_for_context(20, 1100, 2, vec![]));
+
+        // The flow goes on from where it stopped
+        assert_eq!(resumed.reach_waypoint(1200), true);
+        assert_eq!(resumed.has_path_to_target_bb(40), true);
+    }
+
+    // Test whether the paths to the heaviest targets are preferred
+    #[test]
+    fn cfg_weighted_targets() {
+        let id_mapping: HashMap<BbId, HashSet<CmpId>> = [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (30, vec![1200].into_iter().collect())].iter().cloned().collect();
+        let target_info = [(1200, TargetInfo { weight: 3, label: Some("a.c:12 nullPointer".to_string()) })].iter().cloned().collect();
+        let data = CfgFile {
+            targets: vec![1100, 1200].into_iter().collect(),
+            edges: vec![(0,10), (0,30), (10,20)].into_iter().collect(),
+            id_mapping,
+            target_info,
+            ..Default::default()
+        };
+        let cfg = ControlFlowGraph::new(data, Arc::new(HarmonicMean));
+
+        assert_eq!(cfg.target_weight(1100), 1);
+        assert_eq!(cfg.target_label(1200), Some("a.c:12 nullPointer"));
+        assert_eq!(cfg.score_for_bb(30), TARGET_SCORE);
+        assert_eq!(cfg.score_for_bb(20), 2 * WEIGHT_DISTANCE);
+        // The heavy target is closer even though it is as deep as the other
+        assert!(cfg.score_for_bb(30) + 1 < cfg.score_for_bb(10));
+    }
+
+    // Test whether the BBs calling functions which reach a target take the
+    // function level distance of the call graph
+    #[test]
+    fn cfg_call_graph_distance() {
+        // BB 10 of main calls parse, the target is in BB 30 of parse
+        let new_data = || CfgFile {
+            targets: vec![1100].into_iter().collect(),
+            edges: vec![(0,10), (10,40), (20,30)].into_iter().collect(),
+            id_mapping: [(30, vec![1100].into_iter().collect())].iter().cloned().collect(),
+            functions: vec![
+                FunctionInfo {
+                    name: "main".to_string(),
+                    bbs: vec![0, 10, 40].into_iter().collect(),
+                    calls: [(10, vec!["parse".to_string()].into_iter().collect())].iter().cloned().collect(),
+                },
+                FunctionInfo { name: "parse".to_string(), bbs: vec![20, 30].into_iter().collect(), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let cfg = ControlFlowGraph::new(new_data(), Arc::new(HarmonicMean));
+        assert_eq!(cfg.score_for_bb(10), UNDEF_SCORE);
+
+        let mut cfg = ControlFlowGraph::new(new_data(), Arc::new(Aflgo));
+        assert_eq!(cfg.score_for_bb(10), 10);
+        assert_eq!(cfg.score_for_bb(0), 11);
+        assert_eq!(cfg.score_for_bb(40), UNDEF_SCORE);
+
+        cfg.remove_target(1100);
+        assert_eq!(cfg.score_for_bb(10), UNDEF_SCORE);
+        assert_eq!(cfg.score_for_bb(0), UNDEF_SCORE);
+    }
+
+    #[test]
+    fn cfg_context_distance() {
+        // Helper BB 50 returns either to BB 60 on the way to the target or
+        // to the dead end BB 80
+        let id_mapping: HashMap<BbId, HashSet<CmpId>> = [(50, vec![1500].into_iter().collect()), (60, vec![1600].into_iter().collect()), (70, vec![1700].into_iter().collect()), (80, vec![1800].into_iter().collect())].iter().cloned().collect();
+        let data = CfgFile {
+            targets: vec![1700].into_iter().collect(),
+            edges: vec![(50,60), (50,80), (60,70)].into_iter().collect(),
+            id_mapping,
+            ..Default::default()
+        };
+        let mut cfg = ControlFlowGraph::new(data, Arc::new(Greedy));
+        let traces = [vec![(1500, 1), (1600, 0), (1700, 0)], vec![(1500, 2), (1800, 0)]];
+        let score = cfg.score_for_bb(50);
+        assert!(score < UNDEF_SCORE);
+
+        for t in &traces {
+            cfg.learn_contexts(t);
+        }
+        assert_eq!(cfg.score_for_context(50, 1500, 2, vec![]), score);
+
+        cfg.set_context_sensitive(true);
+        for t in &traces {
+            cfg.learn_contexts(t);
+        }
+        assert_eq!(cfg.score_for_context(50, 1500, 1, vec![]), score);
+        assert_eq!(cfg.score_for_context(50, 1500, 2, vec![]), UNDEF_SCORE);
+        assert_eq!(cfg.score_for_context(60, 1600, 0, vec![]), cfg.score_for_bb(60));
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Read, Write};
+use super::cfg::{BbId, CallSiteId, CmpId};
+use super::fparse::{CfgFile, FunctionInfo, TargetInfo};
+
+// Compact binary encoding of the targets file: a magic header followed by
+// the bincode encoding of CompactCfgFile. Whole-program CFGs are hundreds of
+// MB in JSON and slow to parse, mostly because of the edges. The last byte
+// of the header is the version of the encoding.
+pub const MAGIC: &[u8; 8] = b"PSCFG\0\0\x02";
+
+// Edges in compressed sparse row form: the successors of nodes[i] are
+// succs[offsets[i]..offsets[i + 1]]
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CompactCfgFile {
+    targets: Vec<CmpId>,
+    nodes: Vec<BbId>,
+    offsets: Vec<u32>,
+    succs: Vec<BbId>,
+    id_mapping: Vec<(BbId, Vec<CmpId>)>,
+    callsite_dominators: Vec<(CallSiteId, Vec<CmpId>)>,
+    flows: Vec<Vec<Vec<CmpId>>>,
+    target_info: Vec<(CmpId, TargetInfo)>,
+    functions: Vec<CompactFunction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct CompactFunction {
+    name: String,
+    bbs: Vec<BbId>,
+    calls: Vec<(BbId, Vec<String>)>,
+}
+
+// Whether the header is the one of another version of the encoding
+pub fn is_other_version(header: &[u8]) -> bool {
+    let version = MAGIC.len() - 1;
+    header.len() >= MAGIC.len() && header[..version] == MAGIC[..version] && header[version] != MAGIC[version]
+}
+
+fn to_io(e: bincode::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn sorted<T: Ord>(mut v: Vec<T>) -> Vec<T> {
+    v.sort();
+    v
+}
+
+pub fn write_compact<W: Write>(mut writer: W, data: &CfgFile) -> io::Result<()> {
+    let mut adjacency: HashMap<BbId, Vec<BbId>> = HashMap::new();
+    for &(src, dst) in &data.edges {
+        adjacency.entry(src).or_insert(vec![]).push(dst);
+    }
+    let mut compact = CompactCfgFile {
+        targets: sorted(data.targets.iter().cloned().collect()),
+        nodes: sorted(adjacency.keys().cloned().collect()),
+        ..Default::default()
+    };
+    compact.offsets.push(0);
+    for n in &compact.nodes {
+        compact.succs.extend(sorted(adjacency[n].clone()));
+        compact.offsets.push(compact.succs.len() as u32);
+    }
+    compact.id_mapping = sorted(data.id_mapping.iter().map(|(&bb, cmps)| (bb, sorted(cmps.iter().cloned().collect()))).collect());
+    compact.callsite_dominators = sorted(data.callsite_dominators.iter().map(|(&cs, cmps)| (cs, sorted(cmps.iter().cloned().collect()))).collect());
+    compact.flows = data.flows.iter()
+        .map(|f| f.iter().map(|w| sorted(w.iter().cloned().collect())).collect())
+        .collect();
+    compact.target_info = data.target_info.iter().map(|(&c, i)| (c, i.clone())).collect();
+    compact.target_info.sort_by_key(|(c, _)| *c);
+    compact.functions = data.functions.iter()
+        .map(|f| CompactFunction {
+            name: f.name.clone(),
+            bbs: sorted(f.bbs.iter().cloned().collect()),
+            calls: sorted(f.calls.iter().map(|(&bb, callees)| (bb, sorted(callees.iter().cloned().collect()))).collect()),
+        })
+        .collect();
+
+    writer.write_all(MAGIC)?;
+    bincode::serialize_into(&mut writer, &compact).map_err(to_io)?;
+    writer.flush()
+}
+
+// Reads a compact targets file, the magic header has already been read
+pub fn read_compact<R: Read>(reader: R) -> io::Result<CfgFile> {
+    let compact: CompactCfgFile = bincode::deserialize_from(reader).map_err(to_io)?;
+    if compact.offsets.len() != compact.nodes.len() + 1
+        || compact.offsets.last().map_or(true, |&o| o as usize != compact.succs.len()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupted edge list"));
+    }
+
+    let mut edges = HashSet::with_capacity(compact.succs.len());
+    for (i, &src) in compact.nodes.iter().enumerate() {
+        let (begin, end) = (compact.offsets[i] as usize, compact.offsets[i + 1] as usize);
+        if begin > end || end > compact.succs.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupted edge list"));
+        }
+        edges.extend(compact.succs[begin..end].iter().map(|&dst| (src, dst)));
+    }
+
+    Ok(CfgFile {
+        targets: compact.targets.into_iter().collect(),
+        edges,
+        id_mapping: compact.id_mapping.into_iter().map(|(bb, cmps)| (bb, cmps.into_iter().collect())).collect(),
+        callsite_dominators: compact.callsite_dominators.into_iter().map(|(cs, cmps)| (cs, cmps.into_iter().collect())).collect(),
+        flows: compact.flows.into_iter().map(|f| f.into_iter().map(|w| w.into_iter().collect()).collect()).collect(),
+        target_info: compact.target_info.into_iter().collect(),
+        functions: compact.functions.into_iter()
+            .map(|f| FunctionInfo {
+                name: f.name,
+                bbs: f.bbs.into_iter().collect(),
+                calls: f.calls.into_iter().map(|(bb, callees)| (bb, callees.into_iter().collect())).collect(),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_roundtrip() {
+        let data = CfgFile {
+            targets: vec![1100].into_iter().collect(),
+            edges: vec![(0,10), (10,20), (10,30), (30,10)].into_iter().collect(),
+            id_mapping: [(10, vec![1000].into_iter().collect()), (20, vec![1100, 1101].into_iter().collect())].iter().cloned().collect(),
+            callsite_dominators: [(7, vec![1000].into_iter().collect())].iter().cloned().collect(),
+            flows: vec![vec![vec![1000].into_iter().collect(), vec![1100].into_iter().collect()]],
+            target_info: [(1100, TargetInfo { weight: 2, label: Some("a.c:3".to_string()) })].iter().cloned().collect(),
+            functions: vec![FunctionInfo {
+                name: "main".to_string(),
+                bbs: vec![0, 10, 20, 30].into_iter().collect(),
+                calls: [(10, vec!["parse".to_string()].into_iter().collect())].iter().cloned().collect(),
+            }],
+        };
+        let mut buf = vec![];
+        write_compact(&mut buf, &data).unwrap();
+        assert_eq!(&buf[..MAGIC.len()], MAGIC);
+
+        let read = read_compact(&buf[MAGIC.len()..]).unwrap();
+        assert_eq!(read.targets, data.targets);
+        assert_eq!(read.edges, data.edges);
+        assert_eq!(read.id_mapping, data.id_mapping);
+        assert_eq!(read.callsite_dominators, data.callsite_dominators);
+        assert_eq!(read.flows, data.flows);
+        assert_eq!(read.target_info, data.target_info);
+        assert_eq!(read.functions, data.functions);
+
+        assert!(read_compact(&buf[MAGIC.len()..buf.len() - 1]).is_err());
+        assert!(!is_other_version(&buf));
+        assert!(is_other_version(b"PSCFG\0\0\x01"));
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use super::cfg::{CmpId, Score, UNDEF_SCORE};
+
+// Calling context of a cond, the xor of the callsites on the call stack
+pub type Context = u32;
+
+// Context-sensitive correction of the distances. The CFG is
+// inter-procedural, so the BBs of a function called from several places get
+// the distance of the caller closest to a target, whatever the caller. The
+// traces tell which callers actually run after a cond: once the invocation
+// of the function returns, execution goes on in the conds of other contexts,
+// which do not depend on the branch taken by the cond. A cond is as close as
+// its static distance only under the contexts whose continuation gets the
+// closest to a target, and farther away under the others.
+#[derive(Clone, Debug, Default)]
+pub struct ContextScores {
+    // Closest score reached after each cond under each of its contexts
+    continuations: HashMap<CmpId, HashMap<Context, Score>>,
+}
+
+impl ContextScores {
+    // `trace` holds the cmpid, context and static score of the conds of an
+    // input, in execution order
+    pub fn learn(&mut self, trace: &[(CmpId, Context, Score)]) {
+        // Lowest score after the current cond and its context, and the lowest
+        // score of the other contexts
+        let mut best: Option<(Score, Context)> = None;
+        let mut second = UNDEF_SCORE;
+        for &(cmp, ctx, score) in trace.iter().rev() {
+            let after = match best {
+                Some((s, c)) if c != ctx => s,
+                Some(_) => second,
+                None => UNDEF_SCORE,
+            };
+            let entry = self.continuations.entry(cmp).or_insert(HashMap::new())
+                .entry(ctx).or_insert(UNDEF_SCORE);
+            *entry = (*entry).min(after);
+
+            best = match best {
+                Some((s, c)) if c == ctx => Some((s.min(score), c)),
+                Some((s, _)) if score < s => {
+                    second = s;
+                    Some((score, ctx))
+                },
+                Some((s, c)) => {
+                    second = second.min(score);
+                    Some((s, c))
+                },
+                None => Some((score, ctx)),
+            };
+        }
+    }
+
+    // Distance added to the static distance of a cmp under a context, 0
+    // unless the cmp was seen under several contexts
+    pub fn penalty(&self, cmp: CmpId, ctx: Context) -> Score {
+        let contexts = match self.continuations.get(&cmp) {
+            Some(contexts) if contexts.len() > 1 => contexts,
+            _ => return 0,
+        };
+        let closest = contexts.values().cloned().min().unwrap_or(UNDEF_SCORE);
+        if closest == UNDEF_SCORE {
+            // No caller leads anywhere, e.g. the target is in the function
+            return 0;
+        }
+        match contexts.get(&ctx) {
+            Some(&UNDEF_SCORE) => UNDEF_SCORE,
+            Some(&s) => s - closest,
+            None => 0,
+        }
+    }
+
+    pub fn num_contexts(&self) -> usize {
+        self.continuations.values().map(|c| c.len()).sum()
+    }
+
+    pub fn entries(&self) -> Vec<(CmpId, Context, Score)> {
+        let mut entries: Vec<(CmpId, Context, Score)> = self.continuations.iter()
+            .flat_map(|(&cmp, contexts)| contexts.iter().map(move |(&ctx, &s)| (cmp, ctx, s)))
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    pub fn merge(&mut self, entries: Vec<(CmpId, Context, Score)>) {
+        for (cmp, ctx, score) in entries {
+            let entry = self.continuations.entry(cmp).or_insert(HashMap::new())
+                .entry(ctx).or_insert(UNDEF_SCORE);
+            *entry = (*entry).min(score);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_penalty() {
+        let mut scores = ContextScores::default();
+        // Helper cond 100 called from context 1, whose caller then gets to
+        // the target, and from context 2, whose caller has no path to it
+        scores.learn(&[(10, 0, 8), (100, 1, 3), (101, 1, 2), (20, 0, 1), (30, 0, 0)]);
+        scores.learn(&[(10, 0, 8), (100, 2, 3), (101, 2, 2), (40, 0, UNDEF_SCORE)]);
+        scores.learn(&[(10, 0, 8), (100, 3, 3), (50, 0, 6)]);
+
+        assert_eq!(scores.penalty(100, 1), 0);
+        assert_eq!(scores.penalty(100, 2), UNDEF_SCORE);
+        assert_eq!(scores.penalty(100, 3), 6);
+        // Unseen context and single context conds keep the static distance
+        assert_eq!(scores.penalty(100, 4), 0);
+        assert_eq!(scores.penalty(10, 0), 0);
+        // The conds of the same invocation are not a continuation
+        assert_eq!(scores.penalty(101, 2), UNDEF_SCORE);
+        assert_eq!(scores.num_contexts(), 10);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{fs, io, io::Write, path::Path};
+use super::cfg::{BbId, CallSiteId, CmpId, Score};
+
+// What the CFG believes at some point: the distances, the indirect edges
+// and the targets, along with how often the branches were hit
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CfgExport {
+    pub metric: String,
+    pub nodes: Vec<NodeExport>,
+    pub edges: Vec<EdgeExport>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeExport {
+    pub bbid: BbId,
+    pub cmpids: Vec<CmpId>,
+    // None if there is no path to a target
+    pub score: Option<Score>,
+    pub target: bool,
+    pub solved: bool,
+    // Tracked inputs hitting the cmps of the BB (most hit cmp)
+    pub hits: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EdgeExport {
+    pub src: BbId,
+    pub dst: BbId,
+    pub score: Option<Score>,
+    // Callsite of an indirect call edge found at runtime
+    pub callsite: Option<CallSiteId>,
+}
+
+impl CfgExport {
+    // Only keeps the BBs with a path to a target, and the edges between them
+    pub fn retain_reachable(&mut self) {
+        self.nodes.retain(|n| n.score.is_some());
+        self.edges.retain(|e| e.score.is_some());
+    }
+
+    pub fn write_dot<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "digraph cfg {{")?;
+        writeln!(w, "  label=\"distance metric: {}\";", self.metric)?;
+        writeln!(w, "  node [shape=box, fontname=monospace];")?;
+        for n in &self.nodes {
+            let mut label = format!("bb {}", n.bbid);
+            if !n.cmpids.is_empty() {
+                let cmpids: Vec<String> = n.cmpids.iter().map(|c| c.to_string()).collect();
+                label.push_str(&format!("\\ncmp {}", cmpids.join(",")));
+            }
+            label.push_str(&format!("\\nscore {}\\nhits {}", score_label(n.score), n.hits));
+            let mut style = "";
+            if n.solved {
+                label.push_str("\\nsolved");
+                style = ", style=filled, fillcolor=palegreen";
+            } else if n.target {
+                label.push_str("\\ntarget");
+                style = ", style=filled, fillcolor=salmon";
+            } else if n.hits == 0 {
+                style = ", color=gray";
+            }
+            writeln!(w, "  {} [label=\"{}\"{}];", n.bbid, label, style)?;
+        }
+        for e in &self.edges {
+            match e.callsite {
+                Some(cs) => writeln!(w, "  {} -> {} [label=\"{} (cs {})\", style=dashed];",
+                                     e.src, e.dst, score_label(e.score), cs)?,
+                None => writeln!(w, "  {} -> {} [label=\"{}\"];", e.src, e.dst, score_label(e.score))?,
+            }
+        }
+        writeln!(w, "}}")
+    }
+
+    pub fn write_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        serde_json::to_writer(w, self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+
+    pub fn read_json(path: &Path) -> io::Result<CfgExport> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn score_label(score: Option<Score>) -> String {
+    match score {
+        Some(s) => s.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dyncfg::{cfg::ControlFlowGraph, fparse::CfgFile, metric::HarmonicMean};
+    use std::sync::Arc;
+
+    #[test]
+    fn export_dot() {
+        let data = CfgFile {
+            targets: vec![1100].into_iter().collect(),
+            edges: vec![(0,10), (10,20), (10,30)].into_iter().collect(),
+            id_mapping: [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect())].iter().cloned().collect(),
+            ..Default::default()
+        };
+        let mut cfg = ControlFlowGraph::new(data, Arc::new(HarmonicMean));
+        cfg.add_edge((30,40));
+        cfg.set_edge_indirect((30,40), 30);
+        let hits = [(1000, 3)].iter().cloned().collect();
+
+        let mut export = cfg.export(&hits);
+        assert_eq!(export.nodes.len(), 5);
+        let node = export.nodes.iter().find(|n| n.bbid == 10).unwrap();
+        assert_eq!((node.cmpids.clone(), node.hits, node.target), (vec![1000], 3, false));
+        assert_eq!(export.nodes.iter().find(|n| n.bbid == 20).unwrap().score, Some(0));
+        let edge = export.edges.iter().find(|e| e.src == 30).unwrap();
+        assert_eq!((edge.callsite, edge.score), (Some(30), None));
+
+        export.retain_reachable();
+        let mut dot = vec![];
+        export.write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("20 [label=\"bb 20\\ncmp 1100\\nscore 0\\nhits 0\\ntarget\""));
+        assert!(dot.contains("0 -> 10"));
+        assert!(!dot.contains("30 -> 40"));
+    }
+}
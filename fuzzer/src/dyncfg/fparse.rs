@@ -2,16 +2,17 @@ use std::path::Path;
 use std::collections::{HashSet, HashMap};
 use std::io;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader, BufWriter};
 
 use super::cfg::{CmpId, BbId, CallSiteId, Edge};
+use super::compact;
 use serde::de;
 use serde::de::{Deserialize, Deserializer};
 use std::hash::Hash;
 use std::str::FromStr;
 use std::fmt::Display;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct CfgFile {
     pub targets: HashSet<CmpId>,
     #[serde(default)]
@@ -20,6 +21,37 @@ pub struct CfgFile {
     pub id_mapping: HashMap<BbId, HashSet<CmpId>>,
     #[serde(default, deserialize_with = "de_int_key")]
     pub callsite_dominators: HashMap<CallSiteId, HashSet<CmpId>>,
+    #[serde(default)]
+    pub flows: Vec<Vec<HashSet<CmpId>>>,
+    // Weight and label of the targets, targets without one weigh 1
+    #[serde(default, deserialize_with = "de_int_key")]
+    pub target_info: HashMap<CmpId, TargetInfo>,
+    // Static call graph, only used by the aflgo metric
+    #[serde(default)]
+    pub functions: Vec<FunctionInfo>,
+}
+
+// Function of the static call graph: its BBs and the functions each of them
+// calls, by name
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FunctionInfo {
+    pub name: String,
+    #[serde(default)]
+    pub bbs: HashSet<BbId>,
+    #[serde(default, deserialize_with = "de_int_key")]
+    pub calls: HashMap<BbId, HashSet<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TargetInfo {
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+fn default_weight() -> u32 {
+    1
 }
 
 fn de_int_key<'de, D, K, V>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
@@ -40,8 +72,77 @@ where
 
 
 pub fn parse_targets_file(path: &Path) -> io::Result<CfgFile> {
-    let reader = BufReader::new(File::open(path).expect("Could not read targets file"));
-    let result = serde_json::from_reader(reader)?;
+    let with_path = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::open(path).map_err(|e| io::Error::new(e.kind(), with_path(&e)))?;
+    let mut reader = BufReader::new(file);
+
+    // The compact format is detected from its header
+    let header = reader.fill_buf().map_err(|e| io::Error::new(e.kind(), with_path(&e)))?;
+    if compact::is_other_version(header) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  with_path(&"unsupported version of the compact format, regenerate the targets file")));
+    }
+    let is_compact = header.starts_with(compact::MAGIC);
+    let result = if is_compact {
+        reader.consume(compact::MAGIC.len());
+        compact::read_compact(reader).map_err(|e| io::Error::new(e.kind(), with_path(&e)))?
+    } else {
+        serde_json::from_reader(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, with_path(&e)))?
+    };
 
     return Ok(result);
 }
+
+// Writes a targets file in JSON or in the compact format
+pub fn write_targets_file(path: &Path, data: &CfgFile, compact: bool) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    if compact {
+        compact::write_compact(writer, data)
+    } else {
+        serde_json::to_writer(writer, data).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+// Builds the targets from analyzer reports (SARIF or any format supported by
+// the parser) in-process, instead of reading the targets file
+pub fn parse_sarif_targets(sarif: &Path, mapping: &Path, static_cfg: Option<&Path>,
+                           call_graph: Option<&Path>) -> io::Result<CfgFile> {
+    let to_io = |e: Box<dyn std::error::Error>| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+
+    let mut diagnostics = vec![];
+    let opts = parser::Options { code_flow: false, related: false };
+    let (findings, flows) = parser::parse(sarif, None, opts, &mut diagnostics)?;
+    let (mut targets, flows) = parser::filter_and_merge(findings, flows, &parser::Filter::default(), 1, None);
+
+    let map = parser::LineMap::from_file(mapping).map_err(to_io)?;
+    let edges = match static_cfg {
+        Some(path) => parser::targets::read_edges(path).map_err(to_io)?,
+        None => Default::default(),
+    };
+    let targets_file = parser::emit_targets_file(&mut targets, &flows, &map, edges, &mut diagnostics);
+    let functions = match call_graph {
+        Some(path) => parser::targets::read_call_graph(path).map_err(to_io)?,
+        None => vec![],
+    };
+    for d in diagnostics {
+        warn!("{}", d);
+    }
+
+    Ok(CfgFile {
+        targets: targets_file.targets.into_iter().collect(),
+        edges: targets_file.edges.into_iter().collect(),
+        id_mapping: targets_file.id_mapping.into_iter().map(|(bb, cmps)| (bb, cmps.into_iter().collect())).collect(),
+        callsite_dominators: HashMap::new(),
+        flows: targets_file.flows.into_iter().map(|f| f.into_iter().map(|w| w.into_iter().collect()).collect()).collect(),
+        target_info: targets_file.target_info.into_iter()
+            .map(|(cmp, info)| (cmp, TargetInfo { weight: info.weight, label: info.label }))
+            .collect(),
+        functions: functions.into_iter()
+            .map(|f| FunctionInfo {
+                name: f.name,
+                bbs: f.bbs.into_iter().collect(),
+                calls: f.calls.into_iter().map(|(bb, callees)| (bb, callees.into_iter().collect())).collect(),
+            })
+            .collect(),
+    })
+}
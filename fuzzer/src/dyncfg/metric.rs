@@ -0,0 +1,149 @@
+use math::mean;
+use std::sync::Arc;
+use super::cfg::{Score, TARGET_SCORE, UNDEF_SCORE};
+
+// Cost of an indirect call edge found at runtime in the aflgo distance, the
+// factor AFLGo applies to the function level distance
+const CALL_EDGE_WEIGHT: Score = 10;
+
+// Distance of a successor of a basic block
+#[derive(Clone, Copy, Debug)]
+pub struct Successor {
+    pub score: Score,
+    // The edge to the successor is a call edge
+    pub is_call: bool,
+}
+
+// Aggregates the distances of the successors of a basic block into the
+// distance of the basic block
+pub trait DistanceMetric: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn aggregate(&self, succs: &[Successor]) -> Score;
+    // The distance of the basic blocks calling functions comes from the
+    // function level distance of the call graph instead of their successors
+    fn uses_call_graph(&self) -> bool {
+        false
+    }
+}
+
+fn defined(succs: &[Successor]) -> impl Iterator<Item = &Successor> {
+    succs.iter().filter(|s| s.score != UNDEF_SCORE)
+}
+
+// Harmonic mean of the distances of the successors (default)
+pub struct HarmonicMean;
+
+impl DistanceMetric for HarmonicMean {
+    fn name(&self) -> &'static str {
+        "harmonic"
+    }
+
+    fn aggregate(&self, succs: &[Successor]) -> Score {
+        let fvals: Vec<f64> = defined(succs).map(|s| s.score as f64).collect();
+        if fvals.is_empty() {
+            return UNDEF_SCORE;
+        }
+        mean::harmonic(fvals.as_slice()) as Score
+    }
+}
+
+// Distance of the closest successor
+pub struct Greedy;
+
+impl DistanceMetric for Greedy {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn aggregate(&self, succs: &[Successor]) -> Score {
+        match defined(succs).map(|s| s.score).min() {
+            Some(v) => v + 1,
+            None => UNDEF_SCORE,
+        }
+    }
+}
+
+// Sum of the distances of the successors, favors the basic blocks leading
+// to many targets
+pub struct Coverage;
+
+impl DistanceMetric for Coverage {
+    fn name(&self) -> &'static str {
+        "coverage"
+    }
+
+    fn aggregate(&self, succs: &[Successor]) -> Score {
+        let mut vals = defined(succs).map(|s| if s.score == TARGET_SCORE { 1 } else { s.score }).peekable();
+        if vals.peek().is_none() {
+            return UNDEF_SCORE;
+        }
+        vals.fold(0, |acc: Score, v| acc.saturating_add(v))
+    }
+}
+
+// AFLGo's distance: a basic block calling functions is CALL_FACTOR times the
+// function level distance of its closest callee away (see callgraph.rs), the
+// other basic blocks are at the harmonic mean of the distances of their
+// successors plus 1. The call graph only has the direct calls, the indirect
+// call edges found at runtime cost CALL_EDGE_WEIGHT instead of 1.
+pub struct Aflgo;
+
+impl DistanceMetric for Aflgo {
+    fn name(&self) -> &'static str {
+        "aflgo"
+    }
+
+    fn aggregate(&self, succs: &[Successor]) -> Score {
+        let fvals: Vec<f64> = defined(succs)
+            .map(|s| s.score.saturating_add(if s.is_call { CALL_EDGE_WEIGHT } else { 1 }) as f64)
+            .collect();
+        if fvals.is_empty() {
+            return UNDEF_SCORE;
+        }
+        mean::harmonic(fvals.as_slice()) as Score
+    }
+
+    fn uses_call_graph(&self) -> bool {
+        true
+    }
+}
+
+pub static DISTANCE_METRICS: [&str; 4] = ["harmonic", "greedy", "coverage", "aflgo"];
+
+pub fn parse_distance_metric(m: &str) -> Arc<dyn DistanceMetric> {
+    match m {
+        "greedy" => Arc::new(Greedy),
+        "coverage" => Arc::new(Coverage),
+        "aflgo" => Arc::new(Aflgo),
+        _ => Arc::new(HarmonicMean),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn succs(scores: &[Score]) -> Vec<Successor> {
+        scores.iter().map(|&score| Successor { score, is_call: false }).collect()
+    }
+
+    #[test]
+    fn metric_aggregate() {
+        let vals = succs(&[2, 6, UNDEF_SCORE]);
+        assert_eq!(HarmonicMean.aggregate(&vals), 3);
+        assert_eq!(Greedy.aggregate(&vals), 3);
+        assert_eq!(Coverage.aggregate(&vals), 8);
+        assert_eq!(Aflgo.aggregate(&vals), 4);
+
+        let none = succs(&[UNDEF_SCORE]);
+        for name in DISTANCE_METRICS.iter() {
+            let metric = parse_distance_metric(name);
+            assert_eq!(metric.name(), *name);
+            assert_eq!(metric.aggregate(&none), UNDEF_SCORE);
+            assert_eq!(metric.aggregate(&[]), UNDEF_SCORE);
+        }
+
+        let call = [Successor { score: 4, is_call: true }];
+        assert_eq!(Aflgo.aggregate(&call), 14);
+    }
+}
@@ -1,5 +1,12 @@
+pub mod callgraph;
 pub mod cfg;
+pub mod compact;
+pub mod context;
+pub mod export;
 pub mod fparse;
+pub mod metric;
+pub mod state;
+pub mod validate;
 
 #[cfg(test)]
 mod tests {
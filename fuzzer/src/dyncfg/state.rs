@@ -0,0 +1,88 @@
+use std::{fs, io, path::Path};
+use super::cfg::{CallSiteId, CmpId, Edge, FixedBytes, Score};
+use super::context::Context;
+use super::fparse::TargetInfo;
+
+// What the fuzzer learned about the CFG at runtime, saved in the output
+// directory and merged back into the CFG built from the targets file when
+// fuzzing is resumed (`-i -`)
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct CfgState {
+    // Edges found at runtime
+    #[serde(default)]
+    pub edges: Vec<Edge>,
+    // Indirect call edges per callsite
+    #[serde(default)]
+    pub callsite_edges: Vec<(CallSiteId, Vec<Edge>)>,
+    #[serde(default)]
+    pub magic_bytes: Vec<(Edge, FixedBytes)>,
+    #[serde(default)]
+    pub solved_targets: Vec<CmpId>,
+    // Stage of each code flow, in the order of the targets file
+    #[serde(default)]
+    pub flow_stages: Vec<usize>,
+    #[serde(default)]
+    pub reached_waypoints: Vec<CmpId>,
+    // Weights and labels set through the control file
+    #[serde(default)]
+    pub target_info: Vec<(CmpId, TargetInfo)>,
+    // Closest score reached after a cond under a context
+    #[serde(default)]
+    pub contexts: Vec<(CmpId, Context, Score)>,
+    // Kept by the depot rather than the CFG
+    #[serde(default)]
+    pub targets_log: TargetsLogState,
+}
+
+// Progress of the fuzzer on the targets, the inputs are relative to the
+// output directory of the run that found them
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct TargetsLogState {
+    // Seconds the campaign had been running
+    #[serde(default)]
+    pub elapsed: u64,
+    // First input reaching each target and when
+    #[serde(default)]
+    pub witnesses: Vec<(CmpId, String, u64)>,
+    #[serde(default)]
+    pub crashes: Vec<(CmpId, Vec<String>)>,
+    #[serde(default)]
+    pub hits: Vec<(CmpId, usize)>,
+}
+
+// Makes an input relative to another output directory, given the directory
+// of the run that found it relative to the new one. The output directories
+// of the runs are siblings, so the inputs already found in an older run are
+// left as they are.
+pub fn rebase_input(input: &mut String, dir: &Path) {
+    if !input.starts_with("..") {
+        *input = dir.join(&input).display().to_string();
+    }
+}
+
+impl TargetsLogState {
+    pub fn rebase(&mut self, dir: &Path) {
+        let rebase = |input: &mut String| rebase_input(input, dir);
+        for (_, input, _) in self.witnesses.iter_mut() {
+            rebase(input);
+        }
+        for (_, inputs) in self.crashes.iter_mut() {
+            inputs.iter_mut().for_each(rebase);
+        }
+    }
+}
+
+pub fn read_state(path: &Path) -> io::Result<CfgState> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// Written to a temporary file first, so that an interrupted write does not
+// lose the previous state
+pub fn write_state(path: &Path, state: &CfgState) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let f = fs::File::create(&tmp)?;
+    serde_json::to_writer(io::BufWriter::new(f), state)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    fs::rename(tmp, path)
+}
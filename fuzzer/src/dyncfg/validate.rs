@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use super::cfg::{BbId, CmpId};
+use super::fparse::CfgFile;
+
+// Ids listed in a message, the others are only counted
+const MAX_LISTED: usize = 10;
+
+#[derive(Serialize, Debug, Default)]
+pub struct TargetsStats {
+    pub targets: usize,
+    pub flows: usize,
+    pub edges: usize,
+    pub nodes: usize,
+    // Nodes without predecessor, e.g. function entries
+    pub roots: usize,
+    pub mapped_bbs: usize,
+    pub mapped_cmpids: usize,
+    // Nodes with a path to a target
+    pub nodes_to_targets: usize,
+}
+
+// Problems found in a targets file: errors make fuzzing pointless, the
+// warnings only limit how well the fuzzer is guided
+#[derive(Debug, Default)]
+pub struct Validation {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    pub stats: TargetsStats,
+}
+
+impl Validation {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = &self.stats;
+        writeln!(f, "targets: {}, flows: {}", s.targets, s.flows)?;
+        writeln!(f, "cfg: {} nodes, {} edges, {} roots, {} nodes with a path to a target",
+                 s.nodes, s.edges, s.roots, s.nodes_to_targets)?;
+        writeln!(f, "id mapping: {} bbs, {} cmpids", s.mapped_bbs, s.mapped_cmpids)?;
+        for e in &self.errors {
+            writeln!(f, "error: {}", e)?;
+        }
+        for w in &self.warnings {
+            writeln!(f, "warning: {}", w)?;
+        }
+        Ok(())
+    }
+}
+
+fn listed(ids: &mut Vec<u32>) -> String {
+    ids.sort();
+    let mut l: Vec<String> = ids.iter().take(MAX_LISTED).map(|i| i.to_string()).collect();
+    if ids.len() > MAX_LISTED {
+        l.push(format!("... ({} more)", ids.len() - MAX_LISTED));
+    }
+    l.join(", ")
+}
+
+// Nodes reached by a BFS from the start nodes
+fn reach(start: Vec<BbId>, adjacency: &HashMap<BbId, Vec<BbId>>) -> HashSet<BbId> {
+    let mut seen: HashSet<BbId> = start.iter().cloned().collect();
+    let mut worklist: VecDeque<BbId> = start.into_iter().collect();
+    while let Some(n) = worklist.pop_front() {
+        for &m in adjacency.get(&n).into_iter().flatten() {
+            if seen.insert(m) {
+                worklist.push_back(m);
+            }
+        }
+    }
+    seen
+}
+
+pub fn validate(data: &CfgFile) -> Validation {
+    let mut v = Validation::default();
+
+    let mut succs: HashMap<BbId, Vec<BbId>> = HashMap::new();
+    let mut preds: HashMap<BbId, Vec<BbId>> = HashMap::new();
+    for &(src, dst) in &data.edges {
+        succs.entry(src).or_insert(vec![]).push(dst);
+        preds.entry(dst).or_insert(vec![]).push(src);
+    }
+    let nodes: HashSet<BbId> = succs.keys().chain(preds.keys()).cloned().collect();
+    let roots: Vec<BbId> = nodes.iter().filter(|n| !preds.contains_key(n)).cloned().collect();
+
+    let mut cmp_bbs: HashMap<CmpId, Vec<BbId>> = HashMap::new();
+    for (&bb, cmps) in &data.id_mapping {
+        for &cmp in cmps {
+            cmp_bbs.entry(cmp).or_insert(vec![]).push(bb);
+        }
+    }
+
+    let flow_cmps: HashSet<CmpId> = data.flows.iter().flatten().flatten().cloned().collect();
+    v.stats = TargetsStats {
+        targets: data.targets.len(),
+        flows: data.flows.len(),
+        edges: data.edges.len(),
+        nodes: nodes.len(),
+        roots: roots.len(),
+        mapped_bbs: data.id_mapping.len(),
+        mapped_cmpids: cmp_bbs.len(),
+        nodes_to_targets: 0,
+    };
+
+    if data.targets.is_empty() && flow_cmps.is_empty() {
+        v.errors.push("no target".to_string());
+    }
+
+    // The fuzzer is still guided to the mapped targets
+    let mut unmapped: Vec<CmpId> = data.targets.iter().filter(|t| !cmp_bbs.contains_key(t)).cloned().collect();
+    let any_mapped = data.targets.iter().chain(flow_cmps.iter()).any(|t| cmp_bbs.contains_key(t));
+    if !unmapped.is_empty() {
+        let msg = format!("{} targets are not in id_mapping: {}", unmapped.len(), listed(&mut unmapped));
+        if any_mapped {
+            v.warnings.push(msg);
+        } else {
+            v.errors.push(msg);
+        }
+    }
+
+    let mut duplicated: Vec<CmpId> = cmp_bbs.iter().filter(|(_, bbs)| bbs.len() > 1).map(|(&c, _)| c).collect();
+    if !duplicated.is_empty() {
+        v.warnings.push(format!("{} cmpids are mapped to several bbs, only one is used: {}",
+                                duplicated.len(), listed(&mut duplicated)));
+    }
+
+    // Targets the static CFG cannot guide the fuzzer to
+    let target_bbs: Vec<BbId> = data.targets.iter().chain(flow_cmps.iter())
+        .filter_map(|t| cmp_bbs.get(t))
+        .flatten()
+        .cloned()
+        .collect();
+    let from_roots = reach(roots, &succs);
+    let mut outside: Vec<CmpId> = vec![];
+    let mut unreachable: Vec<CmpId> = vec![];
+    for t in &data.targets {
+        match cmp_bbs.get(t) {
+            Some(bbs) if !bbs.iter().any(|bb| nodes.contains(bb)) => outside.push(*t),
+            Some(bbs) if !bbs.iter().any(|bb| from_roots.contains(bb)) => unreachable.push(*t),
+            _ => (),
+        }
+    }
+    if !outside.is_empty() {
+        v.warnings.push(format!("{} targets are in no edge of the CFG: {}", outside.len(), listed(&mut outside)));
+    }
+    if !unreachable.is_empty() {
+        v.warnings.push(format!("{} targets are unreachable from the CFG roots: {}",
+                                unreachable.len(), listed(&mut unreachable)));
+    }
+    v.stats.nodes_to_targets = reach(target_bbs, &preds).iter().filter(|n| nodes.contains(n)).count();
+
+    let mut orphans: Vec<BbId> = data.id_mapping.keys().filter(|bb| !nodes.contains(bb)).cloned().collect();
+    if !orphans.is_empty() && !nodes.is_empty() {
+        v.warnings.push(format!("{} bbs of id_mapping are in no edge of the CFG, their cmpids get no distance: {}",
+                                orphans.len(), listed(&mut orphans)));
+    }
+
+    let mut dominators: Vec<CmpId> = data.callsite_dominators.values().flatten()
+        .filter(|c| !cmp_bbs.contains_key(c))
+        .cloned()
+        .collect::<HashSet<CmpId>>()
+        .into_iter()
+        .collect();
+    if !dominators.is_empty() {
+        v.warnings.push(format!("{} callsite dominators are not in id_mapping: {}",
+                                dominators.len(), listed(&mut dominators)));
+    }
+
+    let mut waypoints: Vec<CmpId> = flow_cmps.iter().filter(|c| !cmp_bbs.contains_key(c)).cloned().collect();
+    if !waypoints.is_empty() {
+        v.warnings.push(format!("{} code flow waypoints are not in id_mapping: {}",
+                                waypoints.len(), listed(&mut waypoints)));
+    }
+    let empty_stages = data.flows.iter().flatten().filter(|w| w.is_empty()).count();
+    if empty_stages > 0 {
+        v.warnings.push(format!("{} code flow waypoints have no cmpid", empty_stages));
+    }
+
+    let mut info: Vec<CmpId> = data.target_info.keys()
+        .filter(|c| !data.targets.contains(c) && !flow_cmps.contains(c))
+        .cloned()
+        .collect();
+    if !info.is_empty() {
+        v.warnings.push(format!("{} cmpids of target_info are not targets: {}", info.len(), listed(&mut info)));
+    }
+
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dyncfg::fparse::TargetInfo;
+
+    #[test]
+    fn validate_targets() {
+        let data = CfgFile {
+            targets: vec![1100, 1200, 1300, 1900].into_iter().collect(),
+            edges: vec![(0,10), (10,20), (30,40), (40,30)].into_iter().collect(),
+            id_mapping: [(10, vec![1000].into_iter().collect()), (20, vec![1100].into_iter().collect()), (40, vec![1200].into_iter().collect()), (50, vec![1300, 1000].into_iter().collect())].iter().cloned().collect(),
+            callsite_dominators: [(7, vec![1000, 1800].into_iter().collect())].iter().cloned().collect(),
+            target_info: [(1700, TargetInfo { weight: 2, label: None })].iter().cloned().collect(),
+            ..Default::default()
+        };
+        let v = validate(&data);
+        assert_eq!(v.is_ok(), true);
+        assert_eq!(v.warnings, vec![
+            "1 targets are not in id_mapping: 1900",
+            "1 cmpids are mapped to several bbs, only one is used: 1000",
+            "1 targets are in no edge of the CFG: 1300",
+            "1 targets are unreachable from the CFG roots: 1200",
+            "1 bbs of id_mapping are in no edge of the CFG, their cmpids get no distance: 50",
+            "1 callsite dominators are not in id_mapping: 1800",
+            "1 cmpids of target_info are not targets: 1700",
+        ]);
+        assert_eq!((v.stats.nodes, v.stats.roots, v.stats.nodes_to_targets), (5, 1, 5));
+        assert!(v.to_string().starts_with("targets: 4, flows: 0\n"));
+
+        // Nothing to guide the fuzzer to
+        let data = CfgFile { targets: vec![1900].into_iter().collect(), ..data };
+        let v = validate(&data);
+        assert_eq!(v.is_ok(), false);
+        assert_eq!(v.errors, vec!["1 targets are not in id_mapping: 1900"]);
+    }
+}
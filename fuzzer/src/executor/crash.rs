@@ -0,0 +1,361 @@
+use angora_common::config;
+use std::{fmt, os::unix::process::ExitStatusExt, path::Path, process::ExitStatus};
+
+// How a run of the target ended, kept along with its StatusType
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ExitInfo {
+    pub signal: Option<i32>,
+    pub exit_code: Option<i32>,
+}
+
+impl ExitInfo {
+    // From a status returned by waitpid
+    pub fn from_wait_status(status: i32) -> Self {
+        if unsafe { libc::WIFSIGNALED(status) } {
+            ExitInfo { signal: Some(unsafe { libc::WTERMSIG(status) }), exit_code: None }
+        } else if unsafe { libc::WIFEXITED(status) } {
+            ExitInfo { signal: None, exit_code: Some(unsafe { libc::WEXITSTATUS(status) }) }
+        } else {
+            Default::default()
+        }
+    }
+
+    // Pin exits with 128 + the signal of the program it runs
+    pub fn from_exit_status(status: ExitStatus, is_pin_mode: bool) -> Self {
+        match status.code() {
+            Some(code) if is_pin_mode && code > 128 => {
+                ExitInfo { signal: Some(code - 128), exit_code: None }
+            },
+            code => ExitInfo { signal: status.signal(), exit_code: code },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sanitizer {
+    Asan,
+    Msan,
+    Ubsan,
+}
+
+impl Sanitizer {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "AddressSanitizer" => Some(Sanitizer::Asan),
+            "MemorySanitizer" => Some(Sanitizer::Msan),
+            "UndefinedBehaviorSanitizer" => Some(Sanitizer::Ubsan),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Sanitizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Sanitizer::Asan => "asan",
+            Sanitizer::Msan => "msan",
+            Sanitizer::Ubsan => "ubsan",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Classification of a crash. The sanitizer is known from the exit code for
+// MSan, the report kind (e.g. heap-buffer-overflow) only from the report on
+// stderr.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CrashKind {
+    pub signal: Option<i32>,
+    pub exit_code: Option<i32>,
+    pub sanitizer: Option<Sanitizer>,
+    pub report: Option<String>,
+}
+
+impl CrashKind {
+    pub fn new(exit: ExitInfo, uses_asan: bool) -> Self {
+        let sanitizer = match exit.exit_code {
+            Some(code) if uses_asan && code == angora_common::defs::MSAN_ERROR_CODE => {
+                Some(Sanitizer::Msan)
+            },
+            _ => None,
+        };
+        CrashKind {
+            signal: exit.signal,
+            exit_code: exit.exit_code,
+            sanitizer,
+            report: None,
+        }
+    }
+
+    pub fn set_report(&mut self, stderr: &str) {
+        if let Some((sanitizer, report)) = parse_sanitizer_report(stderr) {
+            self.sanitizer = Some(sanitizer);
+            self.report = Some(report);
+        }
+    }
+
+    // Name of the crashes subdirectory and of the virgin map of the crash,
+    // e.g. asan-heap-buffer-overflow, msan or sigsegv
+    pub fn category(&self) -> String {
+        match (&self.sanitizer, &self.report, self.signal, self.exit_code) {
+            (Some(san), Some(report), _, _) => format!("{}-{}", san, report),
+            (Some(san), None, _, _) => san.to_string(),
+            (None, _, Some(sig), _) => signal_name(sig),
+            (None, _, None, Some(code)) => format!("exit-{}", code),
+            _ => "unknown".to_string(),
+        }
+    }
+}
+
+fn signal_name(sig: i32) -> String {
+    let name = match sig {
+        libc::SIGSEGV => "sigsegv",
+        libc::SIGABRT => "sigabrt",
+        libc::SIGBUS => "sigbus",
+        libc::SIGFPE => "sigfpe",
+        libc::SIGILL => "sigill",
+        libc::SIGTRAP => "sigtrap",
+        libc::SIGKILL => "sigkill",
+        _ => return format!("signal-{}", sig),
+    };
+    name.to_string()
+}
+
+// Lowercase words separated by '-', usable as a directory name
+fn slug(s: &str) -> String {
+    s.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// UBSan only names the check in the description of the runtime error
+static UBSAN_KINDS: [(&str, &str); 10] = [
+    ("signed integer overflow", "signed-integer-overflow"),
+    ("unsigned integer overflow", "unsigned-integer-overflow"),
+    ("shift exponent", "shift"),
+    ("left shift of", "shift"),
+    ("division by zero", "division-by-zero"),
+    ("null pointer", "null-pointer"),
+    ("out of bounds", "out-of-bounds"),
+    ("misaligned address", "misaligned"),
+    ("load of value", "invalid-value"),
+    ("unreachable program point", "unreachable"),
+];
+
+// Finds the sanitizer and the kind of the report in the stderr of a crash,
+// from the SUMMARY line:
+//   SUMMARY: AddressSanitizer: heap-buffer-overflow (/path/prog+0x4f5a31)
+//   SUMMARY: UndefinedBehaviorSanitizer: undefined-behavior a.c:3:5 in
+// and, for UBSan, from the last runtime error
+pub fn parse_sanitizer_report(stderr: &str) -> Option<(Sanitizer, String)> {
+    let summary = stderr.lines().rev().find_map(|l| {
+        let rest = &l[l.find("SUMMARY: ")? + "SUMMARY: ".len()..];
+        let mut parts = rest.splitn(2, ": ");
+        let sanitizer = Sanitizer::from_name(parts.next()?)?;
+        Some((sanitizer, parts.next().unwrap_or("")))
+    });
+    let (sanitizer, rest) = summary?;
+
+    let report = if sanitizer == Sanitizer::Ubsan {
+        let error = stderr
+            .lines()
+            .rev()
+            .find_map(|l| l.find("runtime error: ").map(|i| &l[i..]))
+            .unwrap_or("");
+        UBSAN_KINDS
+            .iter()
+            .find(|(desc, _)| error.contains(desc))
+            .map_or("undefined-behavior", |&(_, kind)| kind)
+            .to_string()
+    } else {
+        slug(rest.split_whitespace().next().unwrap_or(""))
+    };
+    if report.is_empty() {
+        return Some((sanitizer, "unknown".to_string()));
+    }
+    Some((sanitizer, report))
+}
+
+// Frames of the sanitizer runtime, the same for every crash
+static IGNORED_FRAMES: [&str; 5] = ["__asan", "__msan", "__ubsan", "__sanitizer", "__interceptor_"];
+
+// Symbolized stack of a crash, from its sanitizer report:
+//   ==42==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x6020...
+//       #0 0x4f5a31 in parse /src/parse.c:12:7
+//       #1 0x4f5c02 in main /src/main.c:30:3
+//       #2 0x7f3c1 in __libc_start_main (/lib/x86_64-linux-gnu/libc.so.6+0x21b96)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrashStack {
+    // Sanitizer error line, without the pid
+    pub message: Option<String>,
+    // "function file:line", or "(module+offset)" without symbols
+    pub frames: Vec<String>,
+}
+
+fn file_name(path: &str) -> &str {
+    Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or(path)
+}
+
+fn parse_frame(line: &str) -> Option<String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 3 || !tokens[0].starts_with('#') || tokens[0][1..].parse::<u32>().is_err() {
+        return None;
+    }
+    let frame = if tokens.len() > 3 && tokens[2] == "in" {
+        let (func, loc) = if tokens.len() > 4 {
+            (tokens[3..tokens.len() - 1].join(" "), tokens[tokens.len() - 1])
+        } else {
+            (tokens[3].to_string(), "")
+        };
+        if IGNORED_FRAMES.iter().any(|p| func.starts_with(p)) {
+            return Some(String::new());
+        }
+        if loc.starts_with('(') {
+            // Module of a function without debug info
+            func
+        } else {
+            // Drop the column, it changes with the sanitizer instrumentation
+            let loc = file_name(loc);
+            let line_loc = loc.splitn(3, ':').take(2).collect::<Vec<_>>().join(":");
+            format!("{} {}", func, line_loc).trim_end().to_string()
+        }
+    } else {
+        let module = tokens[2].trim_start_matches('(').trim_end_matches(')');
+        format!("({})", file_name(module))
+    };
+    Some(frame)
+}
+
+impl CrashStack {
+    // Reads the first stack of the report, None if there is none
+    pub fn parse(stderr: &str) -> Option<Self> {
+        let message = stderr
+            .lines()
+            .find(|l| {
+                (l.contains("ERROR: ") || l.contains("WARNING: ")) && l.contains("Sanitizer")
+                    || l.contains("runtime error: ")
+            })
+            .map(|l| l.trim_start_matches(|c: char| c == '=' || c.is_ascii_digit()).trim().to_string());
+
+        let mut frames = vec![];
+        let mut started = false;
+        for line in stderr.lines() {
+            match parse_frame(line) {
+                Some(frame) => {
+                    // Numbering starts again with the next stack, e.g.
+                    // where the memory was freed
+                    if started && line.trim_start().starts_with("#0 ") {
+                        break;
+                    }
+                    started = true;
+                    if !frame.is_empty() {
+                        frames.push(frame);
+                    }
+                },
+                None if started => break,
+                None => (),
+            }
+        }
+        if frames.is_empty() {
+            return None;
+        }
+        Some(CrashStack { message, frames })
+    }
+
+    pub fn top_frames(&self) -> &[String] {
+        &self.frames[..self.frames.len().min(config::CRASH_STACK_FRAMES)]
+    }
+
+    // FNV-1a of the top frames, stable across runs
+    pub fn hash(&self) -> String {
+        let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+        for frame in self.top_frames() {
+            for b in frame.bytes().chain(std::iter::once(b'\n')) {
+                h ^= b as u64;
+                h = h.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        format!("{:016x}", h)
+    }
+}
+
+impl fmt::Display for CrashStack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref message) = self.message {
+            writeln!(f, "{}", message)?;
+        }
+        for (i, frame) in self.frames.iter().enumerate() {
+            writeln!(f, "#{} {}", i, frame)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crash_category() {
+        let segv = ExitInfo { signal: Some(libc::SIGSEGV), exit_code: None };
+        assert_eq!(CrashKind::new(segv, false).category(), "sigsegv");
+        let msan = ExitInfo { signal: None, exit_code: Some(86) };
+        assert_eq!(CrashKind::new(msan, true).category(), "msan");
+        assert_eq!(CrashKind::new(msan, false).category(), "exit-86");
+
+        let mut asan = CrashKind::new(ExitInfo { signal: Some(libc::SIGABRT), exit_code: None }, true);
+        assert_eq!(asan.category(), "sigabrt");
+        asan.set_report(
+            "==42==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011\n\
+             READ of size 1 at 0x602000000011 thread T0\n\
+             SUMMARY: AddressSanitizer: heap-buffer-overflow (/tmp/prog+0x4f5a31)\n",
+        );
+        assert_eq!(asan.category(), "asan-heap-buffer-overflow");
+        assert_eq!(asan.signal, Some(libc::SIGABRT));
+
+        let mut ubsan = CrashKind::new(ExitInfo { signal: Some(libc::SIGABRT), exit_code: None }, false);
+        ubsan.set_report(
+            "a.c:3:12: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'\n\
+             SUMMARY: UndefinedBehaviorSanitizer: undefined-behavior a.c:3:12 in \n",
+        );
+        assert_eq!(ubsan.category(), "ubsan-signed-integer-overflow");
+
+        // A plain crash keeps its signal
+        let mut plain = CrashKind::new(segv, false);
+        plain.set_report("Segmentation fault\n");
+        assert_eq!(plain.category(), "sigsegv");
+    }
+
+    #[test]
+    fn crash_stack() {
+        let report = "==42==ERROR: AddressSanitizer: heap-use-after-free on address 0x602000000010\n\
+             READ of size 1 at 0x602000000010 thread T0\n\
+             \x20   #0 0x4f5a31 in __asan_memcpy (/tmp/prog+0x4f5a31)\n\
+             \x20   #1 0x4f5b02 in parse /src/parse.c:12:7\n\
+             \x20   #2 0x4f5c02 in main /src/main.c:30:3\n\
+             \x20   #3 0x7f3c1d in __libc_start_main (/lib/x86_64-linux-gnu/libc.so.6+0x21b96)\n\
+             \x20   #4 0x41b2e9 (/tmp/prog+0x41b2e9)\n\
+             \n\
+             freed by thread T0 here:\n\
+             \x20   #0 0x4f0d1 in free (/tmp/prog+0x4f0d1)\n";
+        let stack = CrashStack::parse(report).unwrap();
+        assert_eq!(
+            stack.message.as_deref(),
+            Some("ERROR: AddressSanitizer: heap-use-after-free on address 0x602000000010")
+        );
+        assert_eq!(
+            stack.frames,
+            vec!["parse parse.c:12", "main main.c:30", "__libc_start_main", "(prog+0x41b2e9)"]
+        );
+
+        // Same frames from another build, with other addresses and columns
+        let other = report.replace("0x4f5b02", "0x5f5b02").replace("12:7", "12:9");
+        assert_eq!(CrashStack::parse(&other).unwrap().hash(), stack.hash());
+        let other = report.replace("parse.c:12", "parse.c:14");
+        assert_ne!(CrashStack::parse(&other).unwrap().hash(), stack.hash());
+
+        assert_eq!(CrashStack::parse("Segmentation fault\n"), None);
+    }
+}
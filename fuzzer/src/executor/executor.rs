@@ -11,6 +11,7 @@ use angora_common::{config, defs, tag::TagSeg};
 use std::{
     env,
     collections::HashMap,
+    fs, mem,
     path::Path,
     process::{Command, Stdio},
     sync::{
@@ -30,6 +31,9 @@ pub struct Executor {
     forksrv: Option<Forksrv>,
     depot: Arc<depot::Depot>,
     fd: PipeFd,
+    shm_input: Option<ShmInput>,
+    // How the last run ended
+    last_exit: ExitInfo,
     tmout_cnt: usize,
     invariable_cnt: usize,
     pub last_f: u64,
@@ -72,6 +76,19 @@ impl Executor {
             defs::LD_LIBRARY_PATH_VAR.to_string(),
             cmd.ld_library.clone(),
         );
+        let shm_input = if cmd.shm_input { Some(ShmInput::new()) } else { None };
+        if let Some(ref input) = shm_input {
+            envs.insert(
+                defs::INPUT_SHM_ENV_VAR.to_string(),
+                input.get_id().to_string(),
+            );
+        }
+        if cmd.persistent_max > 0 {
+            envs.insert(
+                defs::PERSISTENT_MAX_VAR.to_string(),
+                cmd.persistent_max.to_string(),
+            );
+        }
         let dfsan_options = env::var(defs::DFSAN_OPTIONS_VAR);
         if dfsan_options.is_ok() {
             envs.insert(
@@ -102,6 +119,8 @@ impl Executor {
             forksrv,
             depot,
             fd,
+            shm_input,
+            last_exit: Default::default(),
             tmout_cnt: 0,
             invariable_cnt: 0,
             last_f: defs::UNREACHABLE,
@@ -116,6 +135,13 @@ impl Executor {
         self.is_directed = b;
     }
 
+    pub fn set_time_limit(&mut self, time_limit: u64) {
+        self.cmd.time_limit = time_limit;
+        if let Some(ref mut fs) = self.forksrv {
+            fs.set_time_limit(time_limit);
+        }
+    }
+
     pub fn rebind_forksrv(&mut self) {
         {
             // delete the old forksrv
@@ -220,9 +246,14 @@ impl Executor {
             self.fd.rewind();
         }
         compiler_fence(Ordering::SeqCst);
-        let unmem_status =
-            self.run_target(&self.cmd.main, config::MEM_LIMIT_TRACK, self.cmd.time_limit);
+        let (unmem_status, exit) = self.run_target_exit(
+            &self.cmd.main,
+            config::MEM_LIMIT_TRACK,
+            self.cmd.time_limit,
+            Stdio::null(),
+        );
         compiler_fence(Ordering::SeqCst);
+        self.last_exit = exit;
 
         // find difference
         if unmem_status != StatusType::Normal {
@@ -232,21 +263,156 @@ impl Executor {
                 unmem_status
             );
             // crash or hang
-            if self.branches.has_new(unmem_status).0 {
+            if unmem_status == StatusType::Crash {
+                let kind = self.crash_kind();
+                if self.branches.has_new_crash(&kind.category()).0 {
+                    self.save_crash(buf, cmpid, kind);
+                }
+            } else if self.branches.has_new(unmem_status).0 {
                 self.depot.save(unmem_status, &buf, cmpid);
             }
         }
         skip
     }
 
-    fn do_if_has_new(&mut self, buf: &Vec<u8>, status: StatusType, _explored: bool, cmpid: u32) {
+    // Runs a timed out input again with a longer time limit, returns its
+    // status if it does not hang
+    fn verify_hang(&mut self, buf: &Vec<u8>) -> StatusType {
+        self.write_test_file(buf);
+        self.branches.clear_trace();
+        let hang_limit = command::hang_time_limit(self.cmd.time_limit);
+        compiler_fence(Ordering::SeqCst);
+        let (status, exit) =
+            self.run_target_exit(&self.cmd.main, self.cmd.mem_limit, hang_limit, Stdio::null());
+        compiler_fence(Ordering::SeqCst);
+        self.last_exit = exit;
+        if status != StatusType::Timeout {
+            debug!("Not a hang with a time limit of {}ms: {:?}", hang_limit, status);
+        }
+        status
+    }
+
+    // Classifies the last crash by its exit status. The sanitizer report
+    // needs to run the input again, which clobbers the trace, so it is only
+    // read once the crash is known to be new, see save_crash.
+    fn crash_kind(&self) -> CrashKind {
+        CrashKind::new(self.last_exit, self.cmd.uses_asan)
+    }
+
+    fn crash_report(&mut self, buf: &Vec<u8>) -> Option<String> {
+        let target = self.cmd.main.clone();
+        let time_limit = command::hang_time_limit(self.cmd.time_limit);
+        self.run_for_stderr(buf, &target, self.cmd.mem_limit, time_limit)
+    }
+
+    // Runs a crash again with symbolization, on the sanitized program if
+    // there is one, and reads the stack from its report
+    fn crash_stack(&mut self, buf: &Vec<u8>) -> Option<CrashStack> {
+        let bin = self.cmd.sanopt_bin.clone().unwrap_or_else(|| self.cmd.main.0.clone());
+        let target = (bin, self.cmd.main.1.clone());
+        let mut envs = self.envs.clone();
+        envs.insert(
+            defs::ASAN_OPTIONS_VAR.to_string(),
+            defs::ASAN_OPTIONS_TRIAGE.to_string(),
+        );
+        envs.insert(
+            defs::MSAN_OPTIONS_VAR.to_string(),
+            defs::MSAN_OPTIONS_TRIAGE.to_string(),
+        );
+        envs.insert(
+            defs::UBSAN_OPTIONS_VAR.to_string(),
+            defs::UBSAN_OPTIONS_TRIAGE.to_string(),
+        );
+        // The sanitizers look for llvm-symbolizer in the PATH
+        if let Ok(path) = env::var(defs::PATH_VAR) {
+            envs.insert(defs::PATH_VAR.to_string(), path);
+        }
+
+        let envs = mem::replace(&mut self.envs, envs);
+        let report = self.run_for_stderr(
+            buf,
+            &target,
+            config::MEM_LIMIT_TRACK,
+            config::TIME_LIMIT_TRACK * 1000,
+        );
+        self.envs = envs;
+        CrashStack::parse(&report?)
+    }
+
+    fn run_for_stderr(
+        &mut self,
+        buf: &Vec<u8>,
+        target: &(String, Vec<String>),
+        mem_limit: u64,
+        time_limit: u64,
+    ) -> Option<String> {
+        let path = format!("{}.stderr", self.cmd.out_file);
+        let file = match fs::File::create(&path) {
+            Ok(f) => f,
+            Err(e) => {
+                warn!("Could not create {}: {:?}", path, e);
+                return None;
+            },
+        };
+        self.write_test_file(buf);
+        self.branches.clear_trace();
+        compiler_fence(Ordering::SeqCst);
+        let (status, _) = self.run_target_exit(target, mem_limit, time_limit, Stdio::from(file));
+        compiler_fence(Ordering::SeqCst);
+        if status != StatusType::Crash {
+            debug!("Crash not reproduced while reading its report: {:?}", status);
+        }
+        fs::read(&path).ok().map(|b| String::from_utf8_lossy(&b).into_owned())
+    }
+
+    // Returns None if the crash is a duplicate of a saved one
+    fn save_crash(&mut self, buf: &Vec<u8>, cmpid: u32, mut kind: CrashKind) -> Option<usize> {
+        if self.cmd.crash_reports {
+            if let Some(report) = self.crash_report(buf) {
+                kind.set_report(&report);
+            }
+        }
+        if !self.cmd.crash_triage {
+            return Some(self.depot.save_crash(buf, cmpid, &kind));
+        }
+        match self.crash_stack(buf) {
+            Some(stack) => self.depot.save_unique_crash(buf, cmpid, &kind, &stack),
+            // Not a sanitizer crash, or no symbols: nothing to compare
+            None => Some(self.depot.save_crash(buf, cmpid, &kind)),
+        }
+    }
+
+    fn do_if_has_new(&mut self, buf: &Vec<u8>, mut status: StatusType, _explored: bool, cmpid: u32) {
         // new edge: one byte in bitmap
-        let (has_new_path, has_new_edge, edge_num) = self.branches.has_new(status);
+        let (mut has_new_path, has_new_edge, edge_num) = if status == StatusType::Crash {
+            self.branches.has_new_crash(&self.crash_kind().category())
+        } else {
+            self.branches.has_new(status)
+        };
+
+        if has_new_path && status == StatusType::Timeout {
+            status = self.verify_hang(buf);
+            match status {
+                StatusType::Timeout => (),
+                StatusType::Crash => {
+                    has_new_path = self.branches.has_new_crash(&self.crash_kind().category()).0;
+                },
+                // Only slow, not worth saving
+                _ => return,
+            }
+        }
 
         if has_new_path {
+            let id = if status == StatusType::Crash {
+                match self.save_crash(buf, cmpid, self.crash_kind()) {
+                    Some(id) => id,
+                    None => return,
+                }
+            } else {
+                self.depot.save(status, &buf, cmpid)
+            };
             self.has_new_path = true;
             self.local_stats.find_new(&status);
-            let id = self.depot.save(status, &buf, cmpid);
 
             if status == StatusType::Normal {
                 self.local_stats.avg_edge_num.update(edge_num as f32);
@@ -319,17 +485,28 @@ impl Executor {
     }
 
     fn run_inner(&mut self, buf: &Vec<u8>) -> StatusType {
-        self.write_test(buf);
+        if self.forksrv.is_some() {
+            self.write_test(buf);
+        } else {
+            self.write_test_file(buf);
+        }
 
         self.branches.clear_trace();
 
         compiler_fence(Ordering::SeqCst);
-        let ret_status = if let Some(ref mut fs) = self.forksrv {
-            fs.run()
+        let (ret_status, exit) = if let Some(ref mut fs) = self.forksrv {
+            let status = fs.run();
+            (status, fs.last_exit)
         } else {
-            self.run_target(&self.cmd.main, self.cmd.mem_limit, self.cmd.time_limit)
+            self.run_target_exit(
+                &self.cmd.main,
+                self.cmd.mem_limit,
+                self.cmd.time_limit,
+                Stdio::null(),
+            )
         };
         compiler_fence(Ordering::SeqCst);
+        self.last_exit = exit;
 
         ret_status
     }
@@ -363,14 +540,14 @@ impl Executor {
 
         let t_now: stats::TimeIns = Default::default();
 
-        self.write_test(buf);
+        self.write_file(buf);
 
         compiler_fence(Ordering::SeqCst);
         let ret_status = self.run_target(
             &self.cmd.track,
             config::MEM_LIMIT_TRACK,
             //self.cmd.time_limit *
-            config::TIME_LIMIT_TRACK,
+            config::TIME_LIMIT_TRACK * 1000,
         );
         compiler_fence(Ordering::SeqCst);
 
@@ -442,10 +619,33 @@ impl Executor {
         }
 
 
+        // Advance the code flows whose current waypoint was reached
+        let reached: Vec<CmpId> = {
+            let dyncfg = self.depot.cfg.read().unwrap();
+            cond_list.iter().map(|c| c.base.cmpid).filter(|&c| dyncfg.is_waypoint(c)).collect()
+        };
+        if !reached.is_empty() {
+            let mut dyncfg = self.depot.cfg.write().unwrap();
+            for cmp in reached {
+                if dyncfg.reach_waypoint(cmp) {
+                    info!("Reached code flow waypoint {}, id: {}", cmp, id);
+                }
+            }
+        }
+
+        self.depot.log_hits(&cond_list.iter().map(|c| c.base.cmpid).collect());
+        if self.depot.cfg.read().unwrap().is_context_sensitive() {
+            let trace: Vec<(CmpId, u32)> = cond_list.iter().map(|c| (c.base.cmpid, c.base.context)).collect();
+            self.depot.cfg.write().unwrap().learn_contexts(&trace);
+        }
+
         for cond in cond_list.iter_mut() {
             let dyncfg = self.depot.cfg.read().unwrap();
             if dyncfg.is_target(cond.base.cmpid) {
                 cond.set_target(true);
+                if self.depot.log_reached(cond.base.cmpid, id) {
+                    self.local_stats.num_targets.count();
+                }
             }
         }
 
@@ -468,6 +668,27 @@ impl Executor {
     }
 
     fn write_test(&mut self, buf: &Vec<u8>) {
+        if let Some(ref mut input) = self.shm_input {
+            if input.write_buf(buf) {
+                return;
+            }
+        }
+        self.write_file(buf);
+    }
+
+    // A program started without the fork server may not be linked with
+    // runtime_fast (e.g. the sanitized program), so the input file is always
+    // written, along with the shared memory to keep them the same
+    fn write_test_file(&mut self, buf: &Vec<u8>) {
+        if let Some(ref mut input) = self.shm_input {
+            input.write_buf(buf);
+        }
+        self.write_file(buf);
+    }
+
+    // The track program always reads the input file, the taint comes from
+    // the file reads
+    fn write_file(&mut self, buf: &Vec<u8>) {
         self.fd.write_buf(buf);
         if self.cmd.is_stdin {
             self.fd.rewind();
@@ -480,6 +701,16 @@ impl Executor {
         mem_limit: u64,
         time_limit: u64,
     ) -> StatusType {
+        self.run_target_exit(target, mem_limit, time_limit, Stdio::null()).0
+    }
+
+    fn run_target_exit(
+        &self,
+        target: &(String, Vec<String>),
+        mem_limit: u64,
+        time_limit: u64,
+        stderr: Stdio,
+    ) -> (StatusType, ExitInfo) {
         let mut cmd = Command::new(&target.0);
         let mut child = cmd
             .args(&target.1)
@@ -487,16 +718,18 @@ impl Executor {
             .env_clear()
             .envs(&self.envs)
             .stdout(Stdio::null())
-            .stderr(Stdio::null())
+            .stderr(stderr)
             .mem_limit(mem_limit.clone())
             .setsid()
             .pipe_stdin(self.fd.as_raw_fd(), self.cmd.is_stdin)
             .spawn()
             .expect("Could not run target");
 
-        let timeout = time::Duration::from_secs(time_limit);
+        let timeout = time::Duration::from_millis(time_limit);
+        let mut exit = ExitInfo::default();
         let ret = match child.wait_timeout(timeout).unwrap() {
             Some(status) => {
+                exit = ExitInfo::from_exit_status(status, self.cmd.mode.is_pin_mode());
                 if let Some(status_code) = status.code() {
                     if (self.cmd.uses_asan && status_code == defs::MSAN_ERROR_CODE)
                         || (self.cmd.mode.is_pin_mode() && status_code > 128)
@@ -517,7 +750,7 @@ impl Executor {
                 StatusType::Timeout
             }
         };
-        ret
+        (ret, exit)
     }
 
     pub fn update_log(&mut self) {
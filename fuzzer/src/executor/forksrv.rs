@@ -24,6 +24,8 @@ pub struct Forksrv {
     pub socket: UnixStream,
     uses_asan: bool,
     is_stdin: bool,
+    // How the last child ended
+    pub last_exit: ExitInfo,
 }
 
 impl Forksrv {
@@ -76,24 +78,31 @@ impl Forksrv {
             }
         };
 
-        socket
-            .set_read_timeout(Some(Duration::from_secs(time_limit)))
-            .expect("Couldn't set read timeout");
-        socket
-            .set_write_timeout(Some(Duration::from_secs(time_limit)))
-            .expect("Couldn't set write timeout");
-
         debug!("All right -- Init ForkServer {} successfully!", socket_path);
 
-        Forksrv {
+        let mut fs = Forksrv {
             path: socket_path.to_owned(),
             socket,
             uses_asan,
             is_stdin,
-        }
+            last_exit: Default::default(),
+        };
+        fs.set_time_limit(time_limit);
+        fs
+    }
+
+    // Time limit of the executions in milliseconds
+    pub fn set_time_limit(&mut self, time_limit: u64) {
+        self.socket
+            .set_read_timeout(Some(Duration::from_millis(time_limit)))
+            .expect("Couldn't set read timeout");
+        self.socket
+            .set_write_timeout(Some(Duration::from_millis(time_limit)))
+            .expect("Couldn't set write timeout");
     }
 
     pub fn run(&mut self) -> StatusType {
+        self.last_exit = Default::default();
         if self.socket.write(&FORKSRV_NEW_CHILD).is_err() {
             warn!("Fail to write socket!!");
             return StatusType::Error;
@@ -137,6 +146,11 @@ impl Forksrv {
                         return StatusType::Error;
                     }
                 };
+                // A persistent child stops itself once it is done with an input
+                if unsafe { libc::WIFSTOPPED(status) } {
+                    return StatusType::Normal;
+                }
+                self.last_exit = ExitInfo::from_wait_status(status);
                 let exit_code = unsafe { libc::WEXITSTATUS(status) };
                 let signaled = unsafe { libc::WIFSIGNALED(status) };
                 if signaled || (self.uses_asan && exit_code == MSAN_ERROR_CODE) {
@@ -1,8 +1,15 @@
+mod crash;
 mod executor;
 mod forksrv;
 mod limit;
 mod pipe_fd;
+mod shm_input;
 mod status_type;
 
-use self::pipe_fd::PipeFd;
-pub use self::{executor::Executor, forksrv::Forksrv, status_type::StatusType};
+use self::{pipe_fd::PipeFd, shm_input::ShmInput};
+pub use self::{
+    crash::{CrashKind, CrashStack, ExitInfo, Sanitizer},
+    executor::Executor,
+    forksrv::Forksrv,
+    status_type::StatusType,
+};
@@ -0,0 +1,54 @@
+use angora_common::{
+    config::INPUT_SHM_SIZE,
+    shm,
+    shm_input::{InputBuf, INPUT_IN_FILE},
+};
+
+// Test case written to shared memory, the target reads it through
+// __angora_input_buf (runtime_fast/src/shm_input.rs)
+pub struct ShmInput {
+    input: shm::SHM<InputBuf>,
+}
+
+impl ShmInput {
+    pub fn new() -> Self {
+        let mut input = shm::SHM::<InputBuf>::new();
+        input.len = INPUT_IN_FILE;
+        Self { input }
+    }
+
+    #[inline(always)]
+    pub fn get_id(&self) -> i32 {
+        self.input.get_id()
+    }
+
+    // Returns false if the test case is too large, it has to be written to
+    // the input file instead
+    pub fn write_buf(&mut self, buf: &Vec<u8>) -> bool {
+        if buf.len() > INPUT_SHM_SIZE {
+            self.input.len = INPUT_IN_FILE;
+            return false;
+        }
+        self.input.buf[..buf.len()].copy_from_slice(buf);
+        self.input.len = buf.len() as u32;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shm_input_write() {
+        let mut input = ShmInput::new();
+        let reader = shm::SHM::<InputBuf>::from_id(input.get_id());
+        assert_eq!(reader.len, INPUT_IN_FILE);
+
+        assert!(input.write_buf(&b"abc".to_vec()));
+        assert_eq!((reader.len, &reader.buf[..3]), (3, &b"abc"[..]));
+
+        assert!(!input.write_buf(&vec![0; INPUT_SHM_SIZE + 1]));
+        assert_eq!(reader.len, INPUT_IN_FILE);
+    }
+}
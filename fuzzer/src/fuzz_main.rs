@@ -1,6 +1,12 @@
 use crate::stats::*;
-use crate::dyncfg::{cfg::ControlFlowGraph, fparse::parse_targets_file};
-use angora_common::defs;
+use crate::dyncfg::{
+    cfg::ControlFlowGraph,
+    fparse::{parse_sarif_targets, parse_targets_file, CfgFile},
+    metric::parse_distance_metric,
+    state::{read_state, TargetsLogState},
+    validate::validate,
+};
+use angora_common::{config, defs};
 use chrono::prelude::Local;
 use std::{
     collections::HashMap,
@@ -27,7 +33,7 @@ pub fn fuzz_main(
     pargs: Vec<String>,
     num_jobs: usize,
     mem_limit: u64,
-    time_limit: u64,
+    time_limit: Option<u64>,
     search_method: &str,
     sync_afl: bool,
     enable_afl: bool,
@@ -35,35 +41,68 @@ pub fn fuzz_main(
     cfg_input_file: &str,
     sanopt_target: Option<&str>,
     directed_only: bool,
+    sarif_input: Option<SarifInput>,
+    distance_metric: &str,
+    context_distance: bool,
+    persistent_max: u32,
+    shm_input: bool,
+    crash_reports: bool,
+    crash_triage: bool,
 ) {
     pretty_env_logger::init();
 
     let (seeds_dir, angora_out_dir) = initialize_directories(in_dir, out_dir, sync_afl);
-    let parmesan_info = parse_targets_file(Path::new(&cfg_input_file)).expect("Could not read cfg targets file");
-    let cfg = ControlFlowGraph::new(parmesan_info);
+    let parmesan_info = load_targets(cfg_input_file, sarif_input);
+    let validation = validate(&parmesan_info);
+    for w in &validation.warnings {
+        warn!("Targets: {}", w);
+    }
+    if !validation.is_ok() {
+        for e in &validation.errors {
+            error!("Targets: {}", e);
+        }
+        panic!("Invalid targets file {}, run with --check_targets for details", cfg_input_file);
+    }
+    let mut cfg = ControlFlowGraph::new(parmesan_info, parse_distance_metric(distance_metric));
+    cfg.set_context_sensitive(context_distance);
+    let targets_log = if in_dir == "-" {
+        load_cfg_state(&mut cfg, &seeds_dir)
+    } else {
+        None
+    };
 
-    let command_option = command::CommandOpt::new(
+    let mut command_option = command::CommandOpt::new(
         mode,
         track_target,
         pargs,
         &angora_out_dir,
         search_method,
         mem_limit,
-        time_limit,
+        time_limit.unwrap_or(config::TIME_LIMIT),
         enable_afl,
         enable_exploitation,
         cfg_input_file,
         sanopt_target,
         directed_only,
+        persistent_max,
+        shm_input,
+        crash_reports,
+        crash_triage,
     );
     info!("{:?}", command_option);
 
     check_dep::check_dep(in_dir, out_dir, &command_option);
 
     let depot = Arc::new(depot::Depot::new(seeds_dir, &angora_out_dir, RwLock::new(cfg.clone())));
+    if let Some(log) = targets_log {
+        depot.merge_targets_log(log);
+    }
+    if in_dir == "-" {
+        depot.load_crash_index(depot.dirs.seeds_dir.parent().unwrap());
+    }
     info!("{:?}", depot.dirs);
 
-    let stats = Arc::new(RwLock::new(stats::ChartStats::new()));
+    let stats = Arc::new(RwLock::new(stats::ChartStats::new(cfg.metric_name())));
     let global_branches = Arc::new(branches::GlobalBranches::new(RwLock::new(cfg)));
     let fuzzer_stats = create_stats_file_and_write_pid(&angora_out_dir);
     let running = Arc::new(AtomicBool::new(true));
@@ -91,6 +130,14 @@ pub fn fuzz_main(
     // Enable ParmeSan directed-only mode
     executor.set_directed(directed_only);
 
+    if time_limit.is_none() {
+        let avg_exec_us = executor.local_stats.avg_exec_time.get();
+        command_option.time_limit = command::auto_time_limit(avg_exec_us);
+        executor.set_time_limit(command_option.time_limit);
+        info!("Auto time limit: {}ms, average execution time of the seeds: {}us",
+              command_option.time_limit, avg_exec_us);
+    }
+
     let (handles, child_count) = init_cpus_and_run_fuzzing_threads(
         num_jobs,
         &running,
@@ -156,6 +203,58 @@ fn initialize_directories(in_dir: &str, out_dir: &str, sync_afl: bool) -> (PathB
     (seeds_dir, angora_out_dir)
 }
 
+// Files the targets are built from with --sarif, instead of the targets file
+#[derive(Clone, Copy)]
+pub struct SarifInput<'a> {
+    pub report: &'a str,
+    pub mapping: &'a str,
+    pub static_cfg: Option<&'a str>,
+    pub call_graph: Option<&'a str>,
+}
+
+fn load_targets(cfg_input_file: &str, sarif_input: Option<SarifInput>) -> CfgFile {
+    match sarif_input {
+        Some(input) => {
+            parse_sarif_targets(Path::new(input.report), Path::new(input.mapping),
+                                input.static_cfg.map(Path::new), input.call_graph.map(Path::new))
+                .unwrap_or_else(|e| panic!("Could not read targets from the SARIF report: {}", e))
+        },
+        None => parse_targets_file(Path::new(&cfg_input_file))
+            .unwrap_or_else(|e| panic!("Could not read cfg targets file: {}", e)),
+    }
+}
+
+// Validates the targets without fuzzing, returns false if fuzzing them is
+// pointless
+pub fn check_targets(cfg_input_file: &str, sarif_input: Option<SarifInput>) -> bool {
+    let validation = validate(&load_targets(cfg_input_file, sarif_input));
+    print!("{}", validation);
+    validation.is_ok()
+}
+
+// The state of the previous run is next to its queue directory. Returns the
+// log of the targets, with the inputs rebased on the new output directory.
+fn load_cfg_state(cfg: &mut ControlFlowGraph, seeds_dir: &Path) -> Option<TargetsLogState> {
+    let prev_dir = seeds_dir.parent().unwrap();
+    let path = prev_dir.join(defs::CFG_STATE_FILE);
+    if !path.exists() {
+        warn!("No CFG state to resume from in {:?}", path);
+        return None;
+    }
+    match read_state(&path) {
+        Ok(mut state) => {
+            let mut log = std::mem::take(&mut state.targets_log);
+            log.rebase(&Path::new("..").join(prev_dir.file_name().unwrap()));
+            cfg.merge_state(state);
+            Some(log)
+        },
+        Err(e) => {
+            warn!("Could not read CFG state {:?}: {:?}", path, e);
+            None
+        },
+    }
+}
+
 fn gen_path_afl(out_dir: &str) -> PathBuf {
     let base_path = PathBuf::from(out_dir);
     let create_dir_result = fs::create_dir(&base_path);
@@ -244,6 +343,7 @@ fn main_thread_sync_and_log(
         depot::sync_afl(executor, running.clone(), sync_dir, &mut synced_ids);
     }
     let mut sync_counter = 1;
+    let mut export_counter = 1;
     show_stats(&mut log_file, depot, global_branches, stats);
     while running.load(Ordering::SeqCst) {
         thread::sleep(time::Duration::from_secs(5));
@@ -252,6 +352,12 @@ fn main_thread_sync_and_log(
             depot::sync_afl(executor, running.clone(), sync_dir, &mut synced_ids);
             sync_counter = 12;
         }
+        depot.poll_control();
+        export_counter -= 1;
+        if export_counter <= 0 {
+            depot.dump_cfg_export();
+            export_counter = 12;
+        }
 
         show_stats(&mut log_file, depot, global_branches, stats);
         if Arc::strong_count(&child_count) == 1 {
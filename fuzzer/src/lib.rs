@@ -32,7 +32,9 @@ mod check_dep;
 mod command;
 mod tmpfs;
 
-mod dyncfg;
+pub mod dyncfg;
 //mod directed;
 
-pub use crate::fuzz_main::fuzz_main;
+pub use crate::command::parse_time_limit;
+pub use crate::fuzz_main::{check_targets, fuzz_main, SarifInput};
+pub use crate::dyncfg::metric::DISTANCE_METRICS;
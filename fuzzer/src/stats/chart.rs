@@ -22,6 +22,7 @@ pub struct ChartStats {
     num_hangs: Counter,
     num_crashes: Counter,
     num_targets: Counter,
+    distance_metric: String,
 
     fuzz: FuzzStats,
     search: SearchStats,
@@ -29,8 +30,11 @@ pub struct ChartStats {
 }
 
 impl ChartStats {
-    pub fn new() -> Self {
-        Default::default()
+    pub fn new(distance_metric: &str) -> Self {
+        ChartStats {
+            distance_metric: distance_metric.to_string(),
+            ..Default::default()
+        }
     }
 
     pub fn sync_from_local(&mut self, local: &mut LocalStats) {
@@ -53,7 +57,7 @@ impl ChartStats {
         self.num_hangs += local.num_hangs;
         st.num_crashes += local.num_crashes;
         self.num_crashes += local.num_crashes;
-        //self.num_targets += local.num_targets;
+        self.num_targets += local.num_targets;
 
         //local.clear();
     }
@@ -153,7 +157,7 @@ impl fmt::Display for ChartStats {
   COVERAGE |    EDGE: {},   DENSITY: {}%
     EXECS  |   TOTAL: {},     ROUND: {},     MAX_R: {}
     SPEED  |  PERIOD: {:6}r/s    TIME: {}us, 
-    FOUND  |    PATH: {},     HANGS: {},   CRASHES: {} 
+    FOUND  |    PATH: {},     HANGS: {},   CRASHES: {},   TARGETS: {}
 {}
 {}
 {}
@@ -176,6 +180,7 @@ impl fmt::Display for ChartStats {
             self.num_inputs,
             self.num_hangs,
             self.num_crashes,
+            self.num_targets,
             " -- FUZZ -- ".blue().bold(),
             self.fuzz,
             " -- SEARCH -- ".blue().bold(),
@@ -9,6 +9,8 @@ pub struct LocalStats {
     pub num_inputs: Counter,
     pub num_hangs: Counter,
     pub num_crashes: Counter,
+    // Targets reached for the first time
+    pub num_targets: Counter,
 
     pub track_time: TimeDuration,
     pub start_time: TimeIns,
@@ -28,6 +30,7 @@ impl LocalStats {
         self.num_inputs = Default::default();
         self.num_hangs = Default::default();
         self.num_crashes = Default::default();
+        self.num_targets = Default::default();
 
         self.start_time = Default::default();
         self.track_time = Default::default();
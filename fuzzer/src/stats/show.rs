@@ -36,4 +36,6 @@ pub fn show_stats(
         )
         .expect("Unable to write!");
     }
+    depot.dump_targets_status();
+    depot.dump_cfg_state();
 }
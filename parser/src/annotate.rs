@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::sarif::Report;
+use crate::targets::{CmpId, LineMap};
+
+/// uriBaseId of the fuzzer output directory in the annotated log
+pub const FUZZER_OUT_BASE_ID: &str = "FUZZER_OUT";
+
+/// Describes an entry of the `targets_status.json` written by the fuzzer
+/// (`TargetStatus` in `fuzzer/src/depot/status.rs`)
+#[derive(Deserialize, Debug, Default)]
+pub struct TargetStatus {
+    pub cmpid: CmpId,
+    pub reached: bool,
+    pub solved: bool,
+    /// First input reaching the target, relative to the fuzzer output
+    #[serde(default)]
+    pub witness: Option<String>,
+    /// Crashes found while fuzzing the target, relative to the fuzzer output
+    #[serde(default)]
+    pub crashes: Vec<String>,
+}
+
+/// Outcome of the fuzzing campaign for a static result
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// No location of the result could be mapped to a cmpid
+    NotMapped,
+    NotReached,
+    Reached,
+    Crashed,
+}
+
+/// Reads the `targets_status.json` written by the fuzzer
+pub fn read_status(path: &Path) -> Result<HashMap<CmpId, TargetStatus>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let status: Vec<TargetStatus> = serde_json::from_reader(reader)?;
+
+    Ok(status.into_iter().map(|s| (s.cmpid, s)).collect())
+}
+
+/// Combines the status of the cmpids of a result, returns the outcome, the
+/// witness input and the crashing inputs
+pub fn outcome<'a>(cmpids: &BTreeSet<CmpId>, status: &'a HashMap<CmpId, TargetStatus>)
+    -> (Outcome, Option<&'a str>, Vec<&'a str>) {
+    if cmpids.is_empty() {
+        return (Outcome::NotMapped, None, vec![]);
+    }
+
+    let entries: Vec<&TargetStatus> = cmpids.iter().filter_map(|c| status.get(c)).collect();
+    let witness = entries.iter().find_map(|s| s.witness.as_deref());
+    let crashes: Vec<&str> = entries.iter()
+        .flat_map(|s| s.crashes.iter().map(|c| c.as_str()))
+        .collect();
+
+    let outcome = if !crashes.is_empty() {
+        Outcome::Crashed
+    } else if entries.iter().any(|s| s.reached) {
+        Outcome::Reached
+    } else {
+        Outcome::NotReached
+    };
+    (outcome, witness, crashes)
+}
+
+/// Describes an input of the fuzzer output as a SARIF attachment
+fn attachment(text: &str, uri: &str) -> Value {
+    json!({
+        "description": { "text": text },
+        "artifactLocation": { "uri": uri, "uriBaseId": FUZZER_OUT_BASE_ID },
+    })
+}
+
+fn as_object(value: &mut Value) -> &mut Map<String, Value> {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    value.as_object_mut().unwrap()
+}
+
+/// Annotates every result of the raw log with the outcome of the campaign:
+/// the outcome, cmpids, witness and crashes go in the `fuzzing` property and
+/// the inputs are attached to the result. Crash stacks are read from the
+/// `<crash>.stack` files next to the crashing inputs, if any. Returns the
+/// number of results per outcome
+pub fn annotate(raw: &mut Value, report: &Report, map: &LineMap,
+                status: &HashMap<CmpId, TargetStatus>, fuzzer_out: &Path,
+                diagnostics: &mut Vec<String>) -> HashMap<&'static str, usize> {
+    let mut summary = HashMap::new();
+
+    for (ri, run) in report.runs.iter().enumerate() {
+        let raw_run = &mut raw["runs"][ri];
+        let base_ids = as_object(&mut raw_run["originalUriBaseIds"]);
+        base_ids.insert(FUZZER_OUT_BASE_ID.to_string(),
+                        json!({ "uri": format!("file://{}/", fuzzer_out.display()) }));
+
+        for i in 0..run.results.len() {
+            let cmpids: BTreeSet<CmpId> = run.result_findings(i, false, false, diagnostics)
+                .iter()
+                .filter_map(|f| map.lookup(&f.uri, f.line))
+                .flatten()
+                .cloned()
+                .collect();
+            let (outcome, witness, crashes) = outcome(&cmpids, status);
+            let solved = cmpids.iter().any(|c| status.get(c).is_some_and(|s| s.solved));
+
+            let mut attachments = vec![];
+            if let Some(witness) = witness {
+                attachments.push(attachment("Input reaching the result", witness));
+            }
+            let mut crash_entries = vec![];
+            for crash in &crashes {
+                let stack = fs::read_to_string(fuzzer_out.join(format!("{}.stack", crash))).ok();
+                attachments.push(attachment(stack.as_deref().unwrap_or("Crashing input"), crash));
+                crash_entries.push(json!({ "input": crash, "stack": stack }));
+            }
+
+            let raw_result = as_object(&mut raw_run["results"][i]);
+            let properties = as_object(raw_result.entry("properties").or_insert(Value::Null));
+            properties.insert("fuzzing".to_string(), json!({
+                "outcome": outcome,
+                "solved": solved,
+                "cmpids": cmpids,
+                "witness": witness,
+                "crashes": crash_entries,
+            }));
+            if !attachments.is_empty() {
+                let existing = raw_result.entry("attachments").or_insert_with(|| json!([]));
+                if let Some(existing) = existing.as_array_mut() {
+                    existing.extend(attachments);
+                }
+            }
+
+            let name = match outcome {
+                Outcome::NotMapped => "not mapped",
+                Outcome::NotReached => "not reached",
+                Outcome::Reached => "reached",
+                Outcome::Crashed => "crashed",
+            };
+            *summary.entry(name).or_insert(0) += 1;
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(cmpid: CmpId, reached: bool, witness: Option<&str>, crashes: &[&str]) -> TargetStatus {
+        TargetStatus {
+            cmpid,
+            reached,
+            solved: false,
+            witness: witness.map(|w| w.to_string()),
+            crashes: crashes.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn annotate_outcome() {
+        let status: HashMap<CmpId, TargetStatus> = vec![
+            status(1, false, None, &[]),
+            status(2, true, Some("queue/id:000003"), &[]),
+            status(3, true, Some("queue/id:000004"), &["crashes/sigsegv/id:000000"]),
+        ].into_iter().map(|s| (s.cmpid, s)).collect();
+
+        let cmpids = |c: &[CmpId]| c.iter().cloned().collect::<BTreeSet<CmpId>>();
+        assert_eq!(outcome(&cmpids(&[]), &status).0, Outcome::NotMapped);
+        assert_eq!(outcome(&cmpids(&[1]), &status).0, Outcome::NotReached);
+        assert_eq!(outcome(&cmpids(&[1, 2]), &status), (Outcome::Reached, Some("queue/id:000003"), vec![]));
+        assert_eq!(outcome(&cmpids(&[2, 3]), &status).2, vec!["crashes/sigsegv/id:000000"]);
+    }
+}
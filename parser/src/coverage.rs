@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::merge::normalize_path;
+
+/// Describes the export of `llvm-cov export -format=text`
+#[derive(Deserialize, Debug)]
+struct Export {
+    /// Required - Exported objects, usually a single one
+    data: Vec<ExportData>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExportData {
+    /// Required - Coverage of the source files
+    #[serde(default)]
+    files: Vec<ExportFile>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExportFile {
+    /// Required - Path of the source file
+    filename: String,
+    /// Required - Segments as `[line, col, count, hasCount, isRegionEntry, isGapRegion]`,
+    /// `isGapRegion` is missing in older versions
+    #[serde(default)]
+    segments: Vec<Vec<Value>>,
+}
+
+/// Describes a segment of the llvm-cov export
+struct Segment {
+    line: u32,
+    count: u64,
+    has_count: bool,
+    is_region_entry: bool,
+    is_gap_region: bool,
+}
+
+impl Segment {
+    fn from_value(value: &[Value]) -> Option<Segment> {
+        Some(Segment {
+            line: value.first()?.as_u64()? as u32,
+            count: value.get(2)?.as_u64()?,
+            has_count: value.get(3)?.as_bool()?,
+            is_region_entry: value.get(4)?.as_bool()?,
+            is_gap_region: value.get(5).and_then(Value::as_bool).unwrap_or(false),
+        })
+    }
+}
+
+/// Execution counts of the instrumented lines of every source file
+#[derive(Debug, Default)]
+pub struct Coverage {
+    files: HashMap<String, BTreeMap<u32, u64>>,
+    /// Files of the coverage data by the suffixes of their path, made of
+    /// whole components and without leading `/`, e.g. `a.c` and `proj/a.c`
+    /// for `/src/proj/a.c`
+    suffixes: BTreeMap<String, Vec<String>>,
+}
+
+/// Coverage of a target line
+#[derive(Debug, PartialEq)]
+pub enum Reach {
+    /// The line was executed the given number of times
+    Reached(u64),
+    /// The line is instrumented but was never executed
+    NotReached,
+    /// The file is covered but the line has no counter
+    NotInstrumented,
+    /// The file is not part of the coverage data
+    UnknownFile,
+}
+
+/// Describes the decision taken on a target
+#[derive(Serialize, Debug)]
+pub struct Pruned {
+    pub uri: String,
+    pub line: u32,
+    pub kept: bool,
+    pub reason: String,
+}
+
+impl Coverage {
+    /// Reads lcov tracefiles (`.info`) or llvm-cov JSON exports, the format
+    /// is detected from the content
+    pub fn from_file(path: &Path, source_root: Option<&str>) -> Result<Coverage, Box<dyn Error>> {
+        let mut content = String::new();
+        BufReader::new(File::open(path)?).read_to_string(&mut content)?;
+
+        if content.trim_start().starts_with('{') {
+            Coverage::from_llvm_cov(&content, source_root)
+        } else {
+            Coverage::from_lcov(content.as_bytes(), source_root)
+        }
+    }
+
+    /// Reads the `SF:` and `DA:<line>,<count>` records of a lcov tracefile
+    pub fn from_lcov<R: BufRead>(reader: R, source_root: Option<&str>)
+        -> Result<Coverage, Box<dyn Error>> {
+        let mut coverage = Coverage::default();
+        let mut current: Option<String> = None;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if let Some(file) = line.strip_prefix("SF:") {
+                current = Some(normalize_path(file, source_root));
+            } else if let Some(record) = line.strip_prefix("DA:") {
+                let file = current.as_ref()
+                    .ok_or_else(|| format!("line {}: DA record outside of a file", i + 1))?;
+                let mut fields = record.split(',');
+                let (number, count) = match (fields.next(), fields.next()) {
+                    (Some(number), Some(count)) => (number.parse::<u32>()?,
+                                                    count.parse::<f64>()? as u64),
+                    _ => return Err(format!("line {}: malformed DA record", i + 1).into()),
+                };
+                *coverage.files.entry(file.clone()).or_default().entry(number).or_insert(0) += count;
+            } else if line == "end_of_record" {
+                current = None;
+            }
+        }
+
+        coverage.index();
+        Ok(coverage)
+    }
+
+    /// Computes the line counts from the segments of a llvm-cov JSON export,
+    /// the same way `llvm-cov show` does it: a line is executed as many times
+    /// as the regions starting on it or, if none, the region wrapping it
+    pub fn from_llvm_cov(content: &str, source_root: Option<&str>)
+        -> Result<Coverage, Box<dyn Error>> {
+        let export: Export = serde_json::from_str(content)?;
+        let mut coverage = Coverage::default();
+
+        for file in export.data.iter().flat_map(|d| d.files.iter()) {
+            let segments: Vec<Segment> = file.segments.iter()
+                .filter_map(|s| Segment::from_value(s))
+                .collect();
+            let lines = coverage.files.entry(normalize_path(&file.filename, source_root))
+                .or_default();
+
+            let mut wrapped: Option<&Segment> = None;
+            let mut next = 0;
+            let last_line = segments.last().map_or(0, |s| s.line);
+            for line in segments.first().map_or(1, |s| s.line)..=last_line {
+                let start = next;
+                while next < segments.len() && segments[next].line == line {
+                    next += 1;
+                }
+                let starting = &segments[start..next];
+
+                // Lines starting a skipped region, e.g. code disabled by the
+                // preprocessor, have no count
+                let skipped = starting.first().is_some_and(|s| !s.has_count && s.is_region_entry);
+                let mut count = wrapped.filter(|w| w.has_count && !skipped).map(|w| w.count);
+                let regions = starting.iter()
+                    .filter(|s| !skipped && s.has_count && s.is_region_entry && !s.is_gap_region);
+                for s in regions {
+                    count = Some(count.map_or(s.count, |c| c.max(s.count)));
+                }
+                if let Some(count) = count {
+                    let entry = lines.entry(line).or_insert(0);
+                    *entry = (*entry).max(count);
+                }
+
+                if let Some(last) = starting.last() {
+                    wrapped = Some(last);
+                }
+            }
+        }
+
+        coverage.index();
+        Ok(coverage)
+    }
+
+    fn index(&mut self) {
+        self.suffixes.clear();
+        for file in self.files.keys() {
+            let path = file.trim_start_matches('/');
+            let starts = std::iter::once(0).chain(path.match_indices('/').map(|(i, _)| i + 1));
+            for start in starts {
+                self.suffixes.entry(path[start..].to_string()).or_default().push(file.clone());
+            }
+        }
+        for files in self.suffixes.values_mut() {
+            files.sort();
+            files.dedup();
+        }
+    }
+
+    /// Finds the files of the coverage data matching the path of a target,
+    /// which may be relative to the paths of the coverage data or the other
+    /// way around. Only the longest matches are returned, several files
+    /// match if the path is ambiguous.
+    pub fn matches(&self, uri: &str) -> Vec<&str> {
+        if let Some((file, _)) = self.files.get_key_value(uri) {
+            return vec![file.as_str()];
+        }
+        let path = uri.trim_start_matches('/');
+        if let Some(files) = self.suffixes.get(path) {
+            return files.iter().map(String::as_str).collect();
+        }
+        // Coverage paths relative to the target path, the longest first
+        for (i, _) in path.match_indices('/') {
+            let suffix = &path[i + 1..];
+            let files: Vec<&str> = self.suffixes.get(suffix).into_iter().flatten()
+                .filter(|f| f.trim_start_matches('/') == suffix)
+                .map(String::as_str)
+                .collect();
+            if !files.is_empty() {
+                return files;
+            }
+        }
+        vec![]
+    }
+
+    /// Returns the coverage of a source location, in the first of the files
+    /// matching an ambiguous path
+    pub fn reach(&self, uri: &str, line: u32) -> Reach {
+        match self.matches(uri).first().map(|f| &self.files[*f]) {
+            None => Reach::UnknownFile,
+            Some(lines) => match lines.get(&line) {
+                None => Reach::NotInstrumented,
+                Some(0) => Reach::NotReached,
+                Some(&count) => Reach::Reached(count),
+            },
+        }
+    }
+}
+
+/// Decides whether a target is kept: by default only the lines which are
+/// instrumented and never executed are dropped, with `keep_reached` only the
+/// executed lines are kept
+pub fn prune(coverage: &Coverage, uri: &str, line: u32, keep_reached: bool) -> Pruned {
+    let (kept, reason) = match coverage.reach(uri, line) {
+        Reach::Reached(count) => (true, format!("reached {} times", count)),
+        Reach::NotReached => (false, "never reached".to_string()),
+        Reach::NotInstrumented => (!keep_reached, "line not instrumented".to_string()),
+        Reach::UnknownFile => (!keep_reached, "file not in coverage data".to_string()),
+    };
+    let matches = coverage.matches(uri);
+    let reason = if matches.len() > 1 {
+        format!("{} in {}, ambiguous file also matching {}", reason, matches[0], matches[1..].join(", "))
+    } else {
+        reason
+    };
+
+    Pruned { uri: uri.to_string(), line, kept, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coverage_lcov() {
+        let info = "TN:\nSF:/src/proj/a.c\nDA:3,5\nDA:4,0\nend_of_record\n";
+        let coverage = Coverage::from_lcov(info.as_bytes(), Some("/src/proj")).unwrap();
+        assert_eq!(coverage.reach("a.c", 3), Reach::Reached(5));
+        assert_eq!(coverage.reach("/src/proj/a.c", 4), Reach::NotReached);
+        assert_eq!(coverage.reach("a.c", 5), Reach::NotInstrumented);
+        assert_eq!(coverage.reach("b.c", 3), Reach::UnknownFile);
+
+        assert!(prune(&coverage, "a.c", 5, false).kept);
+        assert!(!prune(&coverage, "a.c", 5, true).kept);
+        assert!(!prune(&coverage, "a.c", 4, false).kept);
+    }
+
+    #[test]
+    fn coverage_llvm_cov() {
+        let export = r#"{"type": "llvm.coverage.json.export", "version": "2.0.1",
+            "data": [{"files": [{"filename": "/src/proj/a.c", "segments": [
+                [1, 12, 4, true, true, false],
+                [3, 7, 0, true, true, false],
+                [5, 2, 4, true, false, false],
+                [6, 2, 0, false, false, false]
+            ]}]}]}"#;
+        let coverage = Coverage::from_llvm_cov(export, None).unwrap();
+        assert_eq!(coverage.reach("a.c", 2), Reach::Reached(4));
+        assert_eq!(coverage.reach("a.c", 4), Reach::NotReached);
+        assert_eq!(coverage.reach("a.c", 5), Reach::NotReached);
+        assert_eq!(coverage.reach("a.c", 6), Reach::Reached(4));
+        assert_eq!(coverage.reach("a.c", 7), Reach::NotInstrumented);
+    }
+
+    #[test]
+    fn coverage_lookup() {
+        let info = "SF:/src/proj/lib/a.c\nDA:3,5\nend_of_record\n\
+                    SF:/src/proj/tests/a.c\nDA:3,0\nend_of_record\n\
+                    SF:util/b.c\nDA:1,2\nend_of_record\n";
+        let coverage = Coverage::from_lcov(info.as_bytes(), None).unwrap();
+        assert_eq!(coverage.matches("lib/a.c"), vec!["/src/proj/lib/a.c"]);
+        assert_eq!(coverage.matches("a.c"), vec!["/src/proj/lib/a.c", "/src/proj/tests/a.c"]);
+        assert_eq!(coverage.matches("/build/util/b.c"), vec!["util/b.c"]);
+        assert_eq!(coverage.matches("ib/a.c"), Vec::<&str>::new());
+
+        assert_eq!(coverage.reach("tests/a.c", 3), Reach::NotReached);
+        let pruned = prune(&coverage, "a.c", 3, false);
+        assert!(pruned.kept);
+        assert_eq!(pruned.reason, "reached 5 times in /src/proj/lib/a.c, ambiguous file also matching /src/proj/tests/a.c");
+    }
+}
@@ -0,0 +1,255 @@
+use glob::Pattern;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::finding::{CodeFlow, Finding};
+use crate::merge::Target;
+
+/// Describes the filter configuration file
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Globs of the paths to keep, all paths are kept if empty
+    pub include: Vec<String>,
+    /// Globs of the paths to drop, e.g. vendored code and tests
+    pub exclude: Vec<String>,
+    /// Globs of the rule ids to keep, all rules are kept if empty
+    pub allow_rules: Vec<String>,
+    /// Globs of the rule ids to drop
+    pub deny_rules: Vec<String>,
+    /// Minimum SARIF level: none, note, warning or error
+    pub min_level: Option<String>,
+    /// Maximum number of targets in the same file
+    pub max_per_file: Option<usize>,
+    /// Maximum number of targets in the same function
+    pub max_per_function: Option<usize>,
+}
+
+/// Selects the findings and targets which reach the targets file
+#[derive(Debug, Default)]
+pub struct Filter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    allow_rules: Vec<Pattern>,
+    deny_rules: Vec<Pattern>,
+    min_level: u8,
+    max_per_file: Option<usize>,
+    max_per_function: Option<usize>,
+}
+
+/// Orders the SARIF levels, unknown levels are considered as warnings
+pub(crate) fn level_rank(level: &str) -> u8 {
+    parse_level(level).unwrap_or(2)
+}
+
+fn parse_level(level: &str) -> Option<u8> {
+    match level {
+        "none" => Some(0),
+        "note" => Some(1),
+        "warning" => Some(2),
+        "error" => Some(3),
+        _ => None,
+    }
+}
+
+/// Locations kept so far in each file and function, a location counts once
+/// however many targets and waypoints are on it
+#[derive(Default)]
+struct Counts {
+    per_file: HashMap<String, HashSet<u32>>,
+    per_function: HashMap<String, HashSet<(String, u32)>>,
+}
+
+fn compile(globs: &[String]) -> Result<Vec<Pattern>, glob::PatternError> {
+    globs.iter().map(|g| Pattern::new(g)).collect()
+}
+
+impl Filter {
+    /// Reads the filter configuration in JSON format
+    pub fn from_file(path: &Path) -> Result<Filter, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let config: FilterConfig = serde_json::from_reader(reader)?;
+
+        Filter::new(config)
+    }
+
+    /// Fails on an invalid glob or an unknown minimum level
+    pub fn new(config: FilterConfig) -> Result<Filter, Box<dyn Error>> {
+        let min_level = match config.min_level.as_deref() {
+            Some(level) => parse_level(level).ok_or_else(|| {
+                format!("unknown min_level {:?}, expected none, note, warning or error", level)
+            })?,
+            None => 0,
+        };
+        Ok(Filter {
+            include: compile(&config.include)?,
+            exclude: compile(&config.exclude)?,
+            allow_rules: compile(&config.allow_rules)?,
+            deny_rules: compile(&config.deny_rules)?,
+            min_level,
+            max_per_file: config.max_per_file,
+            max_per_function: config.max_per_function,
+        })
+    }
+
+    /// Checks the rule and severity of a finding
+    pub fn keep_finding(&self, finding: &Finding) -> bool {
+        if level_rank(&finding.level) < self.min_level {
+            return false;
+        }
+
+        let rule = finding.rule_id.as_deref().unwrap_or("");
+        if !self.allow_rules.is_empty() && !self.allow_rules.iter().any(|p| p.matches(rule)) {
+            return false;
+        }
+        !self.deny_rules.iter().any(|p| p.matches(rule))
+    }
+
+    /// Checks the path of a target or waypoint
+    pub fn keep_path(&self, uri: &str) -> bool {
+        if !self.include.is_empty() && !self.include.iter().any(|p| p.matches(uri)) {
+            return false;
+        }
+        !self.exclude.iter().any(|p| p.matches(uri))
+    }
+
+    /// Checks the path of a target
+    pub fn keep_target(&self, target: &Target) -> bool {
+        self.keep_path(&target.uri)
+    }
+
+    /// Counts the location unless it exceeds the limits per file and per
+    /// function
+    fn within_limits(&self, counts: &mut Counts, uri: &str, line: u32,
+                     function: Option<&str>) -> bool {
+        let lines = counts.per_file.entry(uri.to_string()).or_default();
+        if !lines.contains(&line) && self.max_per_file.is_some_and(|max| lines.len() >= max) {
+            return false;
+        }
+        let location = (uri.to_string(), line);
+        if let Some(function) = function {
+            let locations = counts.per_function.entry(function.to_string()).or_default();
+            if !locations.contains(&location)
+                && self.max_per_function.is_some_and(|max| locations.len() >= max) {
+                return false;
+            }
+            locations.insert(location);
+        }
+        counts.per_file.entry(uri.to_string()).or_default().insert(line);
+        true
+    }
+
+    /// Drops the targets which don't match the paths or exceed the limits
+    /// per file and per function, the first targets are kept
+    pub fn retain_targets(&self, targets: &mut Vec<Target>) {
+        self.retain(targets, &mut vec![]);
+    }
+
+    /// Drops the targets and the waypoints of the code flows which don't
+    /// match the paths or exceed the limits per file and per function, and
+    /// the flows left without waypoints. The waypoints are staged before the
+    /// other targets so they are kept first, then the first targets are kept
+    pub fn retain(&self, targets: &mut Vec<Target>, flows: &mut Vec<CodeFlow>) {
+        let mut counts = Counts::default();
+
+        for flow in flows.iter_mut() {
+            flow.waypoints.retain(|w| {
+                self.keep_path(&w.uri)
+                    && self.within_limits(&mut counts, &w.uri, w.line, w.function.as_deref())
+            });
+            flow.waypoints.dedup_by(|a, b| a.uri == b.uri && a.line == b.line);
+        }
+        // Flows trimmed to the same waypoints are staged once
+        let mut seen = BTreeSet::new();
+        flows.retain(|f| {
+            let key: Vec<(String, u32)> = f.waypoints.iter().map(|w| (w.uri.clone(), w.line)).collect();
+            !key.is_empty() && seen.insert(key)
+        });
+
+        targets.retain(|t| {
+            let function = t.findings.iter().find_map(|f| f.function.as_deref());
+            self.keep_target(t) && self.within_limits(&mut counts, &t.uri, t.line, function)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{finding::test_finding, merge};
+
+    fn finding(uri: &str, line: u32, rule: &str, level: &str) -> Finding {
+        Finding {
+            rule_id: Some(rule.to_string()),
+            level: level.to_string(),
+            ..test_finding(uri, line, "codeql")
+        }
+    }
+
+    #[test]
+    fn filter_rules_and_level() {
+        let filter = Filter::new(FilterConfig {
+            allow_rules: vec!["cpp/*".to_string()],
+            deny_rules: vec!["cpp/commented-out-code".to_string()],
+            min_level: Some("warning".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert!(filter.keep_finding(&finding("a.c", 1, "cpp/overflow", "error")));
+        assert!(!filter.keep_finding(&finding("a.c", 1, "cpp/overflow", "note")));
+        assert!(!filter.keep_finding(&finding("a.c", 1, "cpp/commented-out-code", "error")));
+        assert!(!filter.keep_finding(&finding("a.c", 1, "js/xss", "error")));
+
+        let typo = Filter::new(FilterConfig { min_level: Some("warn".to_string()), ..Default::default() });
+        assert!(typo.is_err());
+    }
+
+    #[test]
+    fn filter_paths_and_limits() {
+        let filter = Filter::new(FilterConfig {
+            exclude: vec!["third_party/**".to_string(), "tests/*".to_string()],
+            max_per_file: Some(2),
+            ..Default::default()
+        }).unwrap();
+        let findings = vec![
+            finding("src/a.c", 1, "r", "error"),
+            finding("src/a.c", 2, "r", "error"),
+            finding("src/a.c", 3, "r", "error"),
+            finding("third_party/zlib/b.c", 1, "r", "error"),
+            finding("tests/c.c", 1, "r", "error"),
+        ];
+        let mut targets = merge::merge(findings, None);
+        filter.retain_targets(&mut targets);
+        assert_eq!(targets.len(), 2);
+        assert!(targets.iter().all(|t| t.uri == "src/a.c"));
+    }
+
+    #[test]
+    fn filter_flows() {
+        let filter = Filter::new(FilterConfig {
+            exclude: vec!["third_party/**".to_string()],
+            max_per_file: Some(2),
+            ..Default::default()
+        }).unwrap();
+        let flow = |waypoints: &[(&str, u32)]| CodeFlow {
+            waypoints: waypoints.iter().map(|&(uri, line)| finding(uri, line, "r", "error")).collect(),
+        };
+        let flows = vec![
+            flow(&[("src/a.c", 1), ("third_party/zlib/b.c", 5), ("src/a.c", 9)]),
+            flow(&[("third_party/zlib/b.c", 5), ("third_party/zlib/b.c", 7)]),
+        ];
+        let findings = vec![finding("src/a.c", 9, "r", "error"), finding("src/a.c", 12, "r", "error")];
+
+        let (targets, flows) = crate::filter_and_merge(findings, flows, &filter, 1, None);
+        // The excluded waypoint is dropped, the flow left empty too
+        assert_eq!(flows.len(), 1);
+        let waypoints: Vec<(&str, u32)> = flows[0].waypoints.iter().map(|w| (w.uri.as_str(), w.line)).collect();
+        assert_eq!(waypoints, vec![("src/a.c", 1), ("src/a.c", 9)]);
+        // The waypoints count in the limit of the file, once per location
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].line, 9);
+    }
+}
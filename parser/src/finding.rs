@@ -0,0 +1,67 @@
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Describes a location flagged by a static analyzer, along with the
+/// warning which produced it
+#[derive(Serialize, Debug, Clone)]
+pub struct Finding {
+    /// Artifact containing the location
+    pub uri: String,
+    /// Line of the location
+    pub line: u32,
+    /// Column of the location
+    pub column: Option<u32>,
+    /// Last line of the location
+    pub end_line: Option<u32>,
+    /// Function containing the location
+    pub function: Option<String>,
+    /// Name of the analyzer which produced the result
+    pub tool: String,
+    /// Identifier of the rule which was evaluated
+    pub rule_id: Option<String>,
+    /// Severity of the result
+    pub level: String,
+    /// Nature of the result
+    pub kind: String,
+    /// Message reported by the analyzer
+    pub message: String,
+}
+
+/// Builds a warning of a tool at a line, the other fields left empty
+#[cfg(test)]
+pub fn test_finding(uri: &str, line: u32, tool: &str) -> Finding {
+    Finding {
+        uri: uri.to_string(),
+        line,
+        column: None,
+        end_line: None,
+        function: None,
+        tool: tool.to_string(),
+        rule_id: None,
+        level: "warning".to_string(),
+        kind: "fail".to_string(),
+        message: String::new(),
+    }
+}
+
+/// Describes a code flow of a result: the locations the analyzer followed,
+/// in order, from the source to the sink
+#[derive(Serialize, Debug, Clone)]
+pub struct CodeFlow {
+    pub waypoints: Vec<Finding>,
+}
+
+/// Writes the items in JSONL format, one item per line
+pub fn write_jsonl<T: Serialize>(items: &[T], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for item in items {
+        serde_json::to_writer(&mut writer, item)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use serde::Deserialize;
+use std::error::Error;
+use std::path::Path;
+
+use super::{select, Collected, Frontend, Options};
+use crate::finding::Finding;
+
+/// Describes the plist report of the clang static analyzer (scan-build,
+/// `--analyzer-output plist`) and clang-tidy
+#[derive(Deserialize, Debug)]
+struct PlistReport {
+    /// Required - Files referenced by index in the locations
+    #[serde(default)]
+    files: Vec<String>,
+    /// Required - Bugs found by the analyzer
+    #[serde(default)]
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Diagnostic {
+    /// Required - Description of the bug
+    description: String,
+    /// Optional - Checker which found the bug, e.g. core.NullDereference
+    check_name: Option<String>,
+    /// Optional - Bug type, used when the checker is missing
+    #[serde(rename = "type")]
+    bug_type: Option<String>,
+    /// Optional - Function containing the bug
+    issue_context: Option<String>,
+    /// Required - Location of the bug
+    location: PlistLocation,
+    /// Optional - Events and control flow leading to the bug
+    #[serde(default)]
+    path: Vec<PathPiece>,
+    /// Optional - Locations of the notes attached to the bug
+    #[serde(default)]
+    notes: Vec<PathPiece>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PlistLocation {
+    line: u32,
+    col: Option<u32>,
+    /// Index in the files
+    file: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct PathPiece {
+    /// event, control or note
+    kind: Option<String>,
+    location: Option<PlistLocation>,
+}
+
+pub struct PlistFrontend;
+
+impl Frontend for PlistFrontend {
+    fn read(&self, path: &Path, opts: Options, diagnostics: &mut Vec<String>)
+        -> Result<Collected, Box<dyn Error>> {
+        let report: PlistReport = plist::from_file(path)?;
+
+        Ok(collect(report, opts, diagnostics))
+    }
+}
+
+fn collect(report: PlistReport, opts: Options, diagnostics: &mut Vec<String>) -> Collected {
+    let mut collected = (vec![], vec![]);
+
+    for (i, diag) in report.diagnostics.iter().enumerate() {
+        let finding_at = |loc: &PlistLocation, diagnostics: &mut Vec<String>| {
+            let uri = match report.files.get(loc.file) {
+                Some(uri) => uri.clone(),
+                None => {
+                    diagnostics.push(format!("clang diagnostic {}: file index {} out of range",
+                                             i, loc.file));
+                    return None;
+                },
+            };
+            Some(Finding {
+                uri,
+                line: loc.line,
+                column: loc.col,
+                end_line: None,
+                function: diag.issue_context.clone(),
+                tool: "clang".to_string(),
+                rule_id: diag.check_name.clone().or_else(|| diag.bug_type.clone()),
+                level: "warning".to_string(),
+                kind: "fail".to_string(),
+                message: diag.description.clone(),
+            })
+        };
+
+        let primary = finding_at(&diag.location, diagnostics).into_iter().collect();
+        let related = diag.notes.iter()
+            .filter_map(|n| n.location.as_ref())
+            .filter_map(|l| finding_at(l, diagnostics))
+            .collect();
+        let flow = diag.path.iter()
+            .filter(|p| p.kind.as_deref() == Some("event"))
+            .filter_map(|p| p.location.as_ref())
+            .filter_map(|l| finding_at(l, diagnostics))
+            .collect();
+        select(primary, related, flow, opts, &mut collected);
+    }
+    collected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+ <key>files</key>
+ <array><string>src/a.c</string></array>
+ <key>diagnostics</key>
+ <array>
+  <dict>
+   <key>path</key>
+   <array>
+    <dict><key>kind</key><string>event</string>
+     <key>location</key><dict><key>line</key><integer>4</integer><key>col</key><integer>3</integer><key>file</key><integer>0</integer></dict>
+    </dict>
+    <dict><key>kind</key><string>control</string></dict>
+    <dict><key>kind</key><string>event</string>
+     <key>location</key><dict><key>line</key><integer>9</integer><key>col</key><integer>5</integer><key>file</key><integer>0</integer></dict>
+    </dict>
+   </array>
+   <key>description</key><string>Dereference of null pointer</string>
+   <key>category</key><string>Logic error</string>
+   <key>type</key><string>Dereference of null pointer</string>
+   <key>check_name</key><string>core.NullDereference</string>
+   <key>issue_context</key><string>main</string>
+   <key>location</key><dict><key>line</key><integer>9</integer><key>col</key><integer>5</integer><key>file</key><integer>0</integer></dict>
+  </dict>
+ </array>
+</dict>
+</plist>"#;
+
+    #[test]
+    fn clang_plist() {
+        let report: PlistReport = plist::from_bytes(REPORT.as_bytes()).unwrap();
+        let mut diagnostics = vec![];
+        let opts = Options { code_flow: false, related: false };
+        let (findings, _) = collect(report, opts, &mut diagnostics);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].uri, "src/a.c");
+        assert_eq!(findings[0].line, 9);
+        assert_eq!(findings[0].rule_id, Some("core.NullDereference".to_string()));
+        assert_eq!(findings[0].function, Some("main".to_string()));
+
+        let report: PlistReport = plist::from_bytes(REPORT.as_bytes()).unwrap();
+        let opts = Options { code_flow: true, related: false };
+        let (findings, flows) = collect(report, opts, &mut diagnostics);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(flows[0].waypoints[0].line, 4);
+        assert!(diagnostics.is_empty());
+    }
+}
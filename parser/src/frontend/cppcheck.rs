@@ -0,0 +1,99 @@
+use roxmltree::{Document, Node};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use super::{level, select, Collected, Frontend, Options};
+use crate::finding::Finding;
+
+/// Reads the XML report of cppcheck (`--xml`, version 2):
+/// `<results><errors><error id severity msg><location file line column/>...`.
+/// The first location of an error is where it occurs, the following ones
+/// lead to it in reverse order
+pub struct CppcheckFrontend;
+
+impl Frontend for CppcheckFrontend {
+    fn read(&self, path: &Path, opts: Options, diagnostics: &mut Vec<String>)
+        -> Result<Collected, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+
+        collect(&content, opts, diagnostics)
+    }
+}
+
+fn collect(content: &str, opts: Options, diagnostics: &mut Vec<String>)
+    -> Result<Collected, Box<dyn Error>> {
+    let doc = Document::parse(content)?;
+    let root = doc.root_element();
+    if root.tag_name().name() != "results" || root.attribute("version") != Some("2") {
+        return Err("not a cppcheck XML version 2 report".into());
+    }
+
+    let mut collected = (vec![], vec![]);
+    let errors = root.descendants().filter(|n| n.has_tag_name("error"));
+    for (i, error) in errors.enumerate() {
+        let id = error.attribute("id").unwrap_or_default();
+        let finding_at = |loc: Node| {
+            Some(Finding {
+                uri: loc.attribute("file")?.to_string(),
+                line: loc.attribute("line")?.parse().ok()?,
+                column: loc.attribute("column").and_then(|c| c.parse().ok()),
+                end_line: None,
+                function: None,
+                tool: "cppcheck".to_string(),
+                rule_id: Some(id.to_string()),
+                level: level(error.attribute("severity").unwrap_or_default()),
+                kind: "fail".to_string(),
+                message: error.attribute("msg").unwrap_or_default().to_string(),
+            })
+        };
+
+        let locations: Vec<Finding> = error.children()
+            .filter(|n| n.has_tag_name("location"))
+            .filter_map(finding_at)
+            .collect();
+        if locations.is_empty() {
+            diagnostics.push(format!("cppcheck error {} ({}): no location", i, id));
+            continue;
+        }
+
+        let primary = locations[..1].to_vec();
+        let related = locations[1..].to_vec();
+        let flow = locations.into_iter().rev().collect();
+        select(primary, related, flow, opts, &mut collected);
+    }
+    Ok(collected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cppcheck_xml() {
+        let report = r#"<?xml version="1.0" encoding="UTF-8"?>
+<results version="2">
+    <cppcheck version="2.3"/>
+    <errors>
+        <error id="missingIncludeSystem" severity="information" msg="Include file not found."/>
+        <error id="nullPointer" severity="error" msg="Null pointer dereference: p" cwe="476">
+            <location file="src/a.c" line="12" column="5" info="Null pointer dereference"/>
+            <location file="src/a.c" line="10" column="9" info="Assignment p=NULL"/>
+        </error>
+    </errors>
+</results>"#;
+        let mut diagnostics = vec![];
+        let opts = Options { code_flow: false, related: true };
+        let (findings, _) = collect(report, opts, &mut diagnostics).unwrap();
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].line, 12);
+        assert_eq!(findings[0].level, "error");
+        assert_eq!(findings[0].rule_id, Some("nullPointer".to_string()));
+        assert_eq!(diagnostics.len(), 1);
+
+        let opts = Options { code_flow: true, related: false };
+        let (_, flows) = collect(report, opts, &mut diagnostics).unwrap();
+        let lines: Vec<u32> = flows[0].waypoints.iter().map(|w| w.line).collect();
+        assert_eq!(lines, vec![10, 12]);
+    }
+}
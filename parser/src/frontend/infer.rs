@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use super::{level, select, Collected, Frontend, Options};
+use crate::finding::Finding;
+
+/// Describes an issue of the `report.json` written by Infer in `infer-out`
+#[derive(Deserialize, Debug)]
+struct Issue {
+    /// Required - Bug type, e.g. NULL_DEREFERENCE
+    bug_type: String,
+    /// Required - Description of the issue
+    qualifier: String,
+    /// Required - ERROR, WARNING, INFO, ADVICE or LIKE
+    severity: String,
+    /// Required - File containing the issue
+    file: String,
+    line: u32,
+    /// Optional - Column of the issue, -1 if unknown
+    column: Option<i64>,
+    /// Optional - Function containing the issue
+    procedure: Option<String>,
+    /// Optional - Steps leading to the issue
+    #[serde(default)]
+    bug_trace: Vec<TraceStep>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TraceStep {
+    filename: String,
+    line_number: u32,
+    column_number: Option<i64>,
+}
+
+pub struct InferFrontend;
+
+impl Frontend for InferFrontend {
+    fn read(&self, path: &Path, opts: Options, _diagnostics: &mut Vec<String>)
+        -> Result<Collected, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let issues: Vec<Issue> = serde_json::from_reader(reader)?;
+
+        Ok(collect(issues, opts))
+    }
+}
+
+fn collect(issues: Vec<Issue>, opts: Options) -> Collected {
+    let mut collected = (vec![], vec![]);
+
+    for issue in issues {
+        let finding_at = |uri: &str, line: u32, column: Option<i64>| Finding {
+            uri: uri.to_string(),
+            line,
+            column: column.filter(|c| *c > 0).map(|c| c as u32),
+            end_line: None,
+            function: issue.procedure.clone(),
+            tool: "infer".to_string(),
+            rule_id: Some(issue.bug_type.clone()),
+            level: level(&issue.severity),
+            kind: "fail".to_string(),
+            message: issue.qualifier.clone(),
+        };
+
+        let primary = vec![finding_at(&issue.file, issue.line, issue.column)];
+        let flow = issue.bug_trace.iter()
+            .filter(|s| s.line_number > 0)
+            .map(|s| finding_at(&s.filename, s.line_number, s.column_number))
+            .collect();
+        select(primary, vec![], flow, opts, &mut collected);
+    }
+    collected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infer_report() {
+        let issues: Vec<Issue> = serde_json::from_str(r#"[{"bug_type": "NULL_DEREFERENCE",
+            "qualifier": "pointer `p` could be null", "severity": "ERROR", "file": "src/a.c",
+            "line": 12, "column": -1, "procedure": "main", "key": "a.c|main|NULL_DEREFERENCE",
+            "bug_trace": [{"level": 0, "filename": "src/a.c", "line_number": 10, "column_number": 3,
+                           "description": "assigned"},
+                          {"level": 0, "filename": "src/a.c", "line_number": 12, "column_number": -1,
+                           "description": "dereferenced"}]}]"#).unwrap();
+        let (findings, _) = collect(issues, Options { code_flow: false, related: false });
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].column, None);
+        assert_eq!(findings[0].level, "error");
+        assert_eq!(findings[0].function, Some("main".to_string()));
+    }
+}
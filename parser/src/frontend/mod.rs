@@ -0,0 +1,157 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::finding::{CodeFlow, Finding};
+use crate::sarif;
+
+mod clang;
+mod cppcheck;
+mod infer;
+
+/// Report formats of the analyzers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// SARIF 2.1.0 log
+    Sarif,
+    /// Clang static analyzer / scan-build plist
+    Plist,
+    /// Cppcheck XML version 2 (`--xml`)
+    Cppcheck,
+    /// Infer `report.json`
+    Infer,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "sarif" => Ok(Format::Sarif),
+            "plist" => Ok(Format::Plist),
+            "cppcheck" => Ok(Format::Cppcheck),
+            "infer" => Ok(Format::Infer),
+            _ => Err(format!("unknown format {}, expected sarif, plist, cppcheck or infer", s)),
+        }
+    }
+}
+
+/// Options shared by the front-ends
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// Target the locations of the code flows instead of the primary locations
+    pub code_flow: bool,
+    /// Also target the related locations
+    pub related: bool,
+}
+
+/// Findings and code flows read from a report
+pub type Collected = (Vec<Finding>, Vec<CodeFlow>);
+
+/// Front-end converting the reports of an analyzer to the findings model
+pub trait Frontend {
+    /// Reads a report, locations which cannot be resolved to a line are
+    /// reported in diagnostics
+    fn read(&self, path: &Path, opts: Options, diagnostics: &mut Vec<String>)
+        -> Result<Collected, Box<dyn Error>>;
+}
+
+struct SarifFrontend;
+
+impl Frontend for SarifFrontend {
+    fn read(&self, path: &Path, opts: Options, diagnostics: &mut Vec<String>)
+        -> Result<Collected, Box<dyn Error>> {
+        let report = sarif::read_from_file(path)?;
+        let flows = if opts.code_flow {
+            // The locations which were skipped are the ones of the findings
+            sarif::collect_flows(&report, &mut vec![])
+        } else {
+            vec![]
+        };
+        let findings = sarif::collect_findings(report, opts.code_flow, opts.related, diagnostics);
+
+        Ok((findings, flows))
+    }
+}
+
+/// Returns the front-end of a format
+pub fn frontend(format: Format) -> Box<dyn Frontend> {
+    match format {
+        Format::Sarif => Box::new(SarifFrontend),
+        Format::Plist => Box::new(clang::PlistFrontend),
+        Format::Cppcheck => Box::new(cppcheck::CppcheckFrontend),
+        Format::Infer => Box::new(infer::InferFrontend),
+    }
+}
+
+/// Detects the format of a report from its extension, or from its first
+/// bytes for `.json` files and files without extension
+pub fn detect_format(path: &Path) -> Option<Format> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("sarif") => return Some(Format::Sarif),
+        Some("plist") => return Some(Format::Plist),
+        Some("xml") => return Some(Format::Cppcheck),
+        Some("json") | None => (),
+        Some(_) => return None,
+    }
+
+    let mut head = vec![0; 512];
+    let len = File::open(path).and_then(|mut f| f.read(&mut head)).ok()?;
+    let head = String::from_utf8_lossy(&head[..len]);
+    let head = head.trim_start();
+    if head.starts_with('[') {
+        Some(Format::Infer)
+    } else if head.starts_with('{') {
+        Some(Format::Sarif)
+    } else if head.contains("<plist") {
+        Some(Format::Plist)
+    } else if head.contains("<results") {
+        Some(Format::Cppcheck)
+    } else {
+        None
+    }
+}
+
+/// Maps the severities of the analyzers to SARIF levels
+pub fn level(severity: &str) -> String {
+    match severity.to_ascii_lowercase().as_str() {
+        "error" => "error",
+        "style" | "performance" | "portability" | "info" | "advice" | "like" => "note",
+        "information" | "none" => "none",
+        _ => "warning",
+    }.to_string()
+}
+
+/// Selects the findings of a result from its primary location, related
+/// locations and code flow, the same way the SARIF front-end does it
+pub fn select(primary: Vec<Finding>, related: Vec<Finding>, flow: Vec<Finding>, opts: Options,
+              collected: &mut Collected) {
+    if opts.code_flow {
+        collected.0.extend(flow.iter().cloned());
+        if !flow.is_empty() {
+            collected.1.push(CodeFlow { waypoints: flow });
+        }
+    } else {
+        collected.0.extend(primary);
+    }
+    if opts.related {
+        collected.0.extend(related);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frontend_format() {
+        assert_eq!("cppcheck".parse::<Format>(), Ok(Format::Cppcheck));
+        assert!("csv".parse::<Format>().is_err());
+        assert_eq!(detect_format(Path::new("scan/report.plist")), Some(Format::Plist));
+        assert_eq!(detect_format(Path::new("README.md")), None);
+        assert_eq!(level("style"), "note");
+        assert_eq!(level("ERROR"), "error");
+    }
+}
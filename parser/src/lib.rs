@@ -0,0 +1,153 @@
+//! Turns the reports of static analyzers into targets for the fuzzer: the
+//! reports are parsed into findings, filtered, merged into targets and the
+//! targets are emitted as a `file:line` list or as the fuzzer targets file.
+
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+pub mod annotate;
+pub mod coverage;
+pub mod filter;
+pub mod finding;
+pub mod frontend;
+pub mod merge;
+pub mod sarif;
+pub mod targets;
+
+pub use filter::Filter;
+pub use finding::{CodeFlow, Finding};
+pub use frontend::{Format, Options};
+pub use merge::Target;
+pub use targets::{Edge, LineMap, TargetInfo, TargetsFile};
+
+/// Lists the reports of a directory, or the file itself
+pub fn list_reports(input: &Path) -> io::Result<Vec<PathBuf>> {
+    if !input.is_dir() {
+        return Ok(vec![input.to_path_buf()]);
+    }
+
+    let mut paths = vec![];
+    for entry in fs::read_dir(input)? {
+        paths.push(entry?.path());
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Reads a report with the front-end of its format, detected from the file
+/// if not given. Files of unknown format and locations which were skipped
+/// are reported in diagnostics
+pub fn parse_file(path: &Path, format: Option<Format>, opts: Options,
+                  diagnostics: &mut Vec<String>) -> (Vec<Finding>, Vec<CodeFlow>) {
+    let format = match format.or_else(|| frontend::detect_format(path)) {
+        Some(format) => format,
+        None => {
+            diagnostics.push(format!("{}: skipping, unknown report format", path.display()));
+            return (vec![], vec![]);
+        },
+    };
+
+    let mut file_diagnostics = vec![];
+    let collected = frontend::frontend(format).read(path, opts, &mut file_diagnostics);
+    diagnostics.extend(file_diagnostics.into_iter().map(|d| format!("{}: {}", path.display(), d)));
+    match collected {
+        Ok(collected) => collected,
+        Err(why) => {
+            diagnostics.push(format!("{}: couldn't parse {:?} report: {}", path.display(),
+                                     format, why));
+            (vec![], vec![])
+        },
+    }
+}
+
+/// Reads the reports of a file or directory
+pub fn parse(input: &Path, format: Option<Format>, opts: Options,
+             diagnostics: &mut Vec<String>) -> io::Result<(Vec<Finding>, Vec<CodeFlow>)> {
+    let mut findings = vec![];
+    let mut flows = vec![];
+
+    for path in list_reports(input)? {
+        let (mut file_findings, mut file_flows) = parse_file(&path, format, opts, diagnostics);
+        findings.append(&mut file_findings);
+        flows.append(&mut file_flows);
+    }
+    Ok((findings, flows))
+}
+
+/// Filters the findings and merges the ones on the same location into
+/// targets, only keeping the targets flagged by `min_tools` analyzers. The
+/// waypoints of the code flows go through the same path checks and limits
+/// as the targets
+pub fn filter_and_merge(mut findings: Vec<Finding>, mut flows: Vec<CodeFlow>, filter: &Filter,
+                        min_tools: usize, source_root: Option<&str>)
+    -> (Vec<Target>, Vec<CodeFlow>) {
+    findings.retain(|f| filter.keep_finding(f));
+    flows.retain(|f| filter.keep_finding(&f.waypoints[0]));
+
+    // Deduplicate the locations flagged by several analyzers
+    let mut targets = merge::merge(findings, source_root);
+    targets.retain(|t| t.consensus >= min_tools);
+    let mut flows = merge::merge_flows(flows, source_root);
+    filter.retain(&mut targets, &mut flows);
+
+    (targets, flows)
+}
+
+/// Maps the targets to cmpids and builds the targets file, the waypoints of
+/// the code flows are staged instead of being targeted all at once and the
+/// targets are weighted by consensus and severity. The locations without
+/// cmpid are reported in diagnostics
+pub fn emit_targets_file(targets: &mut Vec<Target>, flows: &[CodeFlow], map: &LineMap,
+                         edges: BTreeSet<Edge>, diagnostics: &mut Vec<String>) -> TargetsFile {
+    let mut targets_file = TargetsFile::new(map, edges);
+
+    for flow in flows {
+        if targets_file.add_flow(map, flow) == 0 {
+            let sink = &flow.waypoints[flow.waypoints.len() - 1];
+            diagnostics.push(format!("No cmpid found for the code flow ending at {}:{}",
+                                     sink.uri, sink.line));
+        }
+    }
+    targets.retain(|t| !flows.iter().any(|f| {
+        f.waypoints.iter().any(|w| w.uri == t.uri && w.line == t.line)
+    }));
+
+    for target in targets.iter_mut() {
+        target.cmpids = targets_file.add_target(map, &target.uri, target.line);
+        if target.cmpids.is_empty() {
+            diagnostics.push(format!("No cmpid found for {}:{}", target.uri, target.line));
+        }
+        targets_file.set_info(&target.cmpids, target.weight(), &target.label());
+    }
+    targets_file
+}
+
+/// Writes the targets in `file:line` format
+pub fn write_targets_list(targets: &[Target], path: &Path) -> io::Result<()> {
+    let mut output_file = File::create(path)?;
+
+    for target in targets {
+        writeln!(&mut output_file, "{}:{}", target.uri, target.line)?;
+    }
+    Ok(())
+}
+
+/// Reads a targets list in `file:line` format, malformed lines are reported
+/// in diagnostics
+pub fn read_targets_list(path: &Path, diagnostics: &mut Vec<String>)
+    -> io::Result<Vec<(String, u32)>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut targets = vec![];
+
+    for line in reader.lines() {
+        let line = line?;
+        match line.trim().rsplit_once(':').map(|(uri, l)| (uri, l.parse::<u32>())) {
+            Some((uri, Ok(l))) => targets.push((uri.to_string(), l)),
+            _ if line.trim().is_empty() => (),
+            _ => diagnostics.push(format!("{}: skipping malformed target {}", path.display(), line)),
+        }
+    }
+    Ok(targets)
+}
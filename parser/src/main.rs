@@ -1,224 +1,263 @@
-use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
+use structopt::clap::{AppSettings, Error, ErrorKind};
 use std::fs;
-use std::fs::{File, OpenOptions};
-use std::error::Error;
-use std::io::BufReader;
+use std::fs::File;
 use std::io::Write;
-use std::path::Path;
 use std::path::PathBuf;
 
+use parser::{annotate, coverage, finding, merge, sarif, targets};
+use parser::{Filter, Format, LineMap, Options};
+
 #[derive(Debug, StructOpt)]
-#[structopt(name = "parser")]
+#[structopt(name = "parser",
+            setting = AppSettings::ArgsNegateSubcommands)]
 struct Opt {
 
-    /// SARIF input file
+    /// Analyzer report (SARIF, clang plist, cppcheck XML or Infer JSON), or directory of reports
     #[structopt(parse(from_os_str))]
-    input_path: PathBuf,
+    input_path: Option<PathBuf>,
 
-    /// Output file in txt format
+    /// Output file in txt format, or targets file in JSON format with --mapping
     #[structopt(parse(from_os_str))]
-    output_file: PathBuf,
+    output_file: Option<PathBuf>,
     
     /// Code_flow parsing
     #[structopt(short = "f", long = "flow")]
     code_flow: bool,
-}
 
-/// Describes the structure of a SARIF report
-#[derive(Deserialize, Serialize, Debug)]
-struct Report {
-    /// Required - SARIF reports must have a version - 2.1.0
-    version: String,
-    /// A SARIF log file contains an array of one or more runs
-    runs: Vec<Run>,
-}
+    /// Also target the related locations of the results
+    #[structopt(short = "r", long = "related")]
+    related: bool,
 
-/// Describes the structure of the run, a single invocation of the tool
-#[derive(Deserialize, Serialize, Debug)]
-struct Run {
-    /// Required - The tool which carried out the run
-    tool: Tool,
-    /// Required - The results of the run
-    results: Vec<Results>
-}
+    /// Line to cmpid/bbid mapping (CSV from -idassign-emit-lines), emits the fuzzer targets file
+    #[structopt(short = "m", long = "mapping", parse(from_os_str))]
+    mapping: Option<PathBuf>,
 
-/// Describes the results of the run
-#[derive(Deserialize, Serialize, Debug)]
-#[allow(non_snake_case)]
-struct Results {
-    /// Required - Message string reporting the result
-    message: Message,
-    /// Required - List of location objects 
-    locations: Vec<Location>,
-    /// Required - Code Flow
-    codeFlows: Vec<Flows>,
-}
+    /// Static CFG edges (CSV from -idassign-emit-cfg), only used with --mapping
+    #[structopt(short = "c", long = "cfg", parse(from_os_str), requires = "mapping")]
+    cfg_file: Option<PathBuf>,
 
-/// Describes the location property
-#[derive(Deserialize, Serialize, Debug)]
-#[allow(non_snake_case)]
-struct Location {
-    /// Physical location of the articat
-    physicalLocation: PhysicalLocation,
-}
+    /// Static call graph (CSV from -idassign-emit-callgraph) for the aflgo distance metric, only used with --mapping
+    #[structopt(short = "g", long = "callgraph", parse(from_os_str), requires = "mapping")]
+    callgraph_file: Option<PathBuf>,
 
-/// Describes the location property
-#[derive(Deserialize, Serialize, Debug)]
-#[allow(non_snake_case)]
-struct Flows {
-    threadFlows: Vec<ThreadFlows>,
-}
+    /// Output file for the tools, rules, severity and messages of every target in JSONL format
+    #[structopt(short = "j", long = "jsonl", parse(from_os_str))]
+    jsonl_file: Option<PathBuf>,
 
-#[derive(Deserialize, Serialize, Debug)]
-#[allow(non_snake_case)]
-struct ThreadFlows {
-    locations: Vec<ThreadLocations>,
-}
+    /// Only keep the targets flagged by at least this number of analyzers
+    #[structopt(short = "n", long = "min-tools", default_value = "1")]
+    min_tools: usize,
 
-#[derive(Deserialize, Serialize, Debug)]
-#[allow(non_snake_case)]
-struct ThreadLocations {
-    location: Location,
-}
+    /// Source root stripped from the paths before merging the targets of the analyzers
+    #[structopt(short = "s", long = "source-root")]
+    source_root: Option<String>,
 
-/// Describes the physical location of the error
-#[derive(Deserialize, Serialize, Debug)]
-#[allow(non_snake_case)]
-struct PhysicalLocation {
-    artifactLocation: Artifact,
-    region: Region,
-}
+    /// Filter configuration in JSON format: path globs, rule lists, minimum level and limits
+    #[structopt(short = "F", long = "filter", parse(from_os_str))]
+    filter_file: Option<PathBuf>,
 
-/// Describes the physical artifcat 
-#[derive(Deserialize, Serialize, Debug)]
-struct Artifact {
-    uri: String,
-}
+    /// Format of the reports (sarif, plist, cppcheck or infer), detected from the files by default
+    #[structopt(short = "t", long = "format")]
+    format: Option<Format>,
 
-/// Describes the region in which the error is found
-#[derive(Deserialize, Serialize, Debug)]
-#[allow(non_snake_case)]
-struct Region {
-    startLine: u32,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
 }
 
-/// Describes the message property
-#[derive(Deserialize, Serialize, Debug)]
-struct Message {
-    /// Required - Text describing the message property
-    text: String,
-}
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Prunes a file:line targets list with coverage data (lcov .info or llvm-cov JSON export)
+    Prune {
+        /// Targets in file:line format
+        #[structopt(parse(from_os_str))]
+        targets: PathBuf,
 
-/// Describes the tool which carried out the run
-#[derive(Deserialize, Serialize, Debug)]
-struct Tool {
-    /// Required - Sub-property driver of the tool
-    driver: Driver,
-}
+        /// Coverage data
+        #[structopt(parse(from_os_str))]
+        coverage: PathBuf,
 
-/// Describes the sub-property
-#[derive(Deserialize, Serialize, Debug)]
-struct Driver {
-    /// Required - Name of the tool which produced the analysis
-    name: String
-}
+        /// Output file for the kept targets in file:line format
+        #[structopt(parse(from_os_str))]
+        output_file: PathBuf,
+
+        /// Only keep the targets which were executed, instead of dropping the ones never executed
+        #[structopt(short = "k", long = "keep-reached")]
+        keep_reached: bool,
+
+        /// Output file for the decision taken on every target in JSONL format
+        #[structopt(short = "r", long = "report", parse(from_os_str))]
+        report: Option<PathBuf>,
+
+        /// Source root stripped from the paths of the targets and coverage data
+        #[structopt(short = "s", long = "source-root")]
+        source_root: Option<String>,
+    },
+    /// Annotates the results of a SARIF report with the outcome of a fuzzing campaign
+    Annotate {
+        /// Original SARIF report
+        #[structopt(parse(from_os_str))]
+        sarif_file: PathBuf,
 
+        /// Line to cmpid/bbid mapping used to build the targets file
+        #[structopt(parse(from_os_str))]
+        mapping: PathBuf,
+
+        /// Output directory of the fuzzer, containing targets_status.json
+        #[structopt(parse(from_os_str))]
+        fuzzer_out: PathBuf,
+
+        /// Annotated SARIF report
+        #[structopt(parse(from_os_str))]
+        output_file: PathBuf,
+    },
+}
 
 fn main() {
     let opt = Opt::from_args();
 
-    let input_path = opt.input_path.into_os_string().into_string().unwrap();
-    let output_file = opt.output_file.into_os_string().into_string().unwrap();
-    let input_exist = Path::new(&input_path);
+    match opt.cmd {
+        Some(Command::Prune { targets, coverage, output_file, keep_reached, report,
+                              source_root }) => {
+            prune(targets, coverage, output_file, keep_reached, report, source_root.as_deref());
+            return;
+        },
+        Some(Command::Annotate { sarif_file, mapping, fuzzer_out, output_file }) => {
+            annotate(sarif_file, mapping, fuzzer_out, output_file);
+            return;
+        },
+        None => (),
+    }
+
+    let (input_path, output_file) = match (&opt.input_path, &opt.output_file) {
+        (Some(input_path), Some(output_file)) => (input_path, output_file),
+        _ => Error::with_description("<input-path> and <output-file> are required",
+                                     ErrorKind::MissingRequiredArgument).exit(),
+    };
+    let input_exist = input_path.as_path();
     assert!(input_exist.exists(), "Path doesn't exist");
 
-    if input_exist.is_dir() {
-        match fs::read_dir(input_exist) {
-            Err(why) => println!("! {:?}", why.kind()),
-            Ok(paths) => for path in paths {
-                handle_file(path.unwrap()
-                                .path()
-                                .display()
-                                .to_string()
-                                ,output_file.to_owned()
-                                ,opt.code_flow)
-            },
-        }
-    }
-    else {
-        handle_file(input_path, output_file, opt.code_flow);
+    let filter = match &opt.filter_file {
+        Some(filter_file) => Filter::from_file(filter_file)
+            .unwrap_or_else(|why| panic!("couldn't read filter {}: {}", filter_file.display(), why)),
+        None => Filter::default(),
+    };
+
+    let opts = Options { code_flow: opt.code_flow, related: opt.related };
+    let mut diagnostics = vec![];
+    let (findings, flows) = parser::parse(input_exist, opt.format, opts, &mut diagnostics)
+        .unwrap_or_else(|why| panic!("couldn't read {}: {}", input_exist.display(), why));
+    for diagnostic in diagnostics.drain(..) {
+        eprintln!("{}", diagnostic);
     }
-}
+    let (mut targets, flows) = parser::filter_and_merge(findings, flows, &filter, opt.min_tools,
+                                                        opt.source_root.as_deref());
 
-/// Reads sarif json format from file
-fn read_from_file(file: File) -> Result<Report, Box<dyn Error>> {
-    let reader = BufReader::new(file);
-    let report = serde_json::from_reader(reader)?;
+    match opt.mapping {
+        Some(mapping) => {
+            let map = LineMap::from_file(&mapping)
+                .unwrap_or_else(|why| panic!("couldn't read mapping {}: {}", mapping.display(), why));
+            let edges = match opt.cfg_file {
+                Some(cfg_file) => targets::read_edges(&cfg_file)
+                    .unwrap_or_else(|why| panic!("couldn't read cfg {}: {}", cfg_file.display(), why)),
+                None => Default::default(),
+            };
+            let mut targets_file = parser::emit_targets_file(&mut targets, &flows, &map, edges,
+                                                             &mut diagnostics);
+            if let Some(callgraph_file) = opt.callgraph_file {
+                targets_file.functions = targets::read_call_graph(&callgraph_file)
+                    .unwrap_or_else(|why| panic!("couldn't read call graph {}: {}",
+                                                 callgraph_file.display(), why));
+            }
+            targets_file.write_to_file(output_file)
+                .expect("Failed to write targets file");
+        },
+        None => {
+            // Output format to txt file, next to the SARIF reports
+            let mut output_path = if input_exist.is_dir() {
+                input_exist.join(output_file)
+            } else {
+                input_exist.with_file_name(output_file)
+            };
+            output_path.set_extension("custom_targets.txt");
 
-    Ok(report)
-}
+            parser::write_targets_list(&targets, &output_path).
+                 expect("Failed to write parser output to file");
+        },
+    }
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+    }
 
-/// Write parsed result to file
-fn write_to_file(report: Report, output_path: PathBuf, code_flow: bool)
-    -> std::io::Result<()> {
-    let mut output_file = OpenOptions::new()
-                            .read(true)
-                            .write(true)
-                            .create(true)
-                            .append(true)
-                            .open(output_path).unwrap();
-    
-    // Output version in format: URI Line
-    for r in report.runs {
-        for res in r.results {
-            if !code_flow {
-                for loc in res.locations {
-                    writeln!(&mut output_file, "{}:{}",
-                        loc.physicalLocation.artifactLocation.uri,
-                        loc.physicalLocation.region.startLine,
-                        //res.message.text
-                        ).unwrap();
-                }
-            }
-            else {
-                for flow in res.codeFlows {
-                    for t_flow in flow.threadFlows {
-                        for loc in t_flow.locations {
-                            writeln!(&mut output_file, "{}:{}",
-                                loc.location.physicalLocation.artifactLocation.uri,
-                                loc.location.physicalLocation.region.startLine,
-                                //res.message.text
-                                ).unwrap();
-                        }
-                    }
-                }
-            }
-        }
+    if let Some(jsonl_file) = opt.jsonl_file {
+        finding::write_jsonl(&targets, &jsonl_file)
+            .expect("Failed to write targets to file");
     }
-    Ok(())
 }
 
-fn handle_file(input_path: String, output_file: String, code_flow: bool) {
-    let input_path = Path::new(&input_path);
+/// Keeps the targets reachable according to the coverage data and reports
+/// the reason of every decision
+fn prune(targets_file: PathBuf, coverage_file: PathBuf, output_file: PathBuf,
+         keep_reached: bool, report: Option<PathBuf>, source_root: Option<&str>) {
+    let cov = coverage::Coverage::from_file(&coverage_file, source_root)
+        .unwrap_or_else(|why| panic!("couldn't read coverage {}: {}",
+                                     coverage_file.display(), why));
+    let mut diagnostics = vec![];
+    let targets = parser::read_targets_list(&targets_file, &mut diagnostics)
+        .unwrap_or_else(|why| panic!("couldn't read targets {}: {}",
+                                     targets_file.display(), why));
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+    }
 
-    // Check if file is in sarif extension and open it
-    if input_path.extension().unwrap() == "sarif" {
+    let decisions: Vec<coverage::Pruned> = targets.iter()
+        .map(|(uri, line)| {
+            coverage::prune(&cov, &merge::normalize_path(uri, source_root), *line, keep_reached)
+        })
+        .collect();
 
-    let file = match File::open(&input_path) {
-        Err(why) => panic!("couldn't open {}: {}", input_path.display(), why),
-        Ok(file) => file,
-    };
+    let mut output = File::create(&output_file).expect("Failed to write parser output to file");
+    for (target, decision) in targets.iter().zip(&decisions) {
+        if decision.kept {
+            writeln!(&mut output, "{}:{}", target.0, target.1)
+                .expect("Failed to write parser output to file");
+        } else {
+            eprintln!("Pruned {}:{}: {}", target.0, target.1, decision.reason);
+        }
+    }
+    eprintln!("Kept {} of {} targets", decisions.iter().filter(|d| d.kept).count(),
+              decisions.len());
 
-    // Parse sarif format
-    let report = read_from_file(file).unwrap();
+    if let Some(report) = report {
+        finding::write_jsonl(&decisions, &report)
+            .expect("Failed to write prune report to file");
+    }
+}
 
-    // Output format to txt file
-    let mut output_path = PathBuf::from(input_path);
-    output_path.set_file_name(output_file);
-    output_path.set_extension("custom_targets.txt");
+/// Writes the report with the outcome of the fuzzer for every result
+fn annotate(sarif_file: PathBuf, mapping: PathBuf, fuzzer_out: PathBuf, output_file: PathBuf) {
+    let report = sarif::read_from_file(&sarif_file)
+        .unwrap_or_else(|why| panic!("couldn't parse SARIF {}: {}", sarif_file.display(), why));
+    let mut raw = sarif::read_raw(&sarif_file)
+        .unwrap_or_else(|why| panic!("couldn't parse SARIF {}: {}", sarif_file.display(), why));
+    let map = LineMap::from_file(&mapping)
+        .unwrap_or_else(|why| panic!("couldn't read mapping {}: {}", mapping.display(), why));
+    let fuzzer_out = fs::canonicalize(&fuzzer_out)
+        .unwrap_or_else(|why| panic!("couldn't open {}: {}", fuzzer_out.display(), why));
+    let status_file = fuzzer_out.join("targets_status.json");
+    let status = annotate::read_status(&status_file)
+        .unwrap_or_else(|why| panic!("couldn't read status {}: {}", status_file.display(), why));
 
-    write_to_file(report, output_path, code_flow).
-         expect("Failed to write parser output to file");
+    let mut diagnostics = vec![];
+    let summary = annotate::annotate(&mut raw, &report, &map, &status, &fuzzer_out,
+                                     &mut diagnostics);
+    for diagnostic in diagnostics {
+        eprintln!("{}: {}", sarif_file.display(), diagnostic);
     }
+    for (outcome, count) in summary {
+        eprintln!("{} results {}", count, outcome);
+    }
+
+    let output = File::create(&output_file).expect("Failed to write annotated report to file");
+    serde_json::to_writer_pretty(output, &raw).expect("Failed to write annotated report to file");
 }
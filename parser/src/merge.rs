@@ -0,0 +1,169 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::filter::level_rank;
+use crate::finding::{CodeFlow, Finding};
+use crate::targets::CmpId;
+
+/// Describes a source location flagged by one or more analyzers
+#[derive(Serialize, Debug, Clone)]
+pub struct Target {
+    /// Normalized path of the artifact containing the location
+    pub uri: String,
+    /// Line of the location
+    pub line: u32,
+    /// Analyzers which flagged the location
+    pub tools: BTreeSet<String>,
+    /// Number of analyzers agreeing on the location
+    pub consensus: usize,
+    /// Cmpids the location has been mapped to
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cmpids: Vec<CmpId>,
+    /// Warnings reported on the location
+    pub findings: Vec<Finding>,
+}
+
+impl Target {
+    /// Weight of the target for the fuzzer: the number of analyzers agreeing
+    /// on the location times the rank of the most severe level (note 1,
+    /// warning 2, error 3), at least 1
+    pub fn weight(&self) -> u32 {
+        let rank = self.findings.iter().map(|f| level_rank(&f.level)).max().unwrap_or(0);
+        (self.consensus as u32 * rank as u32).max(1)
+    }
+
+    /// Label of the target for the fuzzer: the rules flagging the location
+    pub fn label(&self) -> String {
+        let rules: BTreeSet<&str> = self.findings.iter()
+            .map(|f| f.rule_id.as_deref().unwrap_or(&f.tool))
+            .collect();
+        format!("{}:{} {}", self.uri, self.line, rules.into_iter().collect::<Vec<_>>().join(","))
+    }
+}
+
+/// Normalizes a path so the same file reported by different analyzers
+/// compares equal: drops the file scheme, the `.` and `..` components and
+/// the source root prefix
+pub fn normalize_path(uri: &str, source_root: Option<&str>) -> String {
+    let uri = uri.trim_start_matches("file://").replace('\\', "/");
+    let is_absolute = uri.starts_with('/');
+
+    let mut components: Vec<&str> = vec![];
+    for c in uri.split('/') {
+        match c {
+            "" | "." => (),
+            ".." => {
+                if matches!(components.last(), None | Some(&"..")) {
+                    if !is_absolute {
+                        components.push(c);
+                    }
+                } else {
+                    components.pop();
+                }
+            },
+            _ => components.push(c),
+        }
+    }
+    let mut path = components.join("/");
+    if is_absolute {
+        path.insert(0, '/');
+    }
+
+    if let Some(root) = source_root {
+        let root = normalize_path(root, None);
+        if let Some(rest) = path.strip_prefix(&root).and_then(|p| p.strip_prefix('/')) {
+            return rest.to_string();
+        }
+    }
+    path
+}
+
+/// Merges the findings on the same `file:line` into a single target,
+/// targets are sorted by decreasing consensus
+pub fn merge(findings: Vec<Finding>, source_root: Option<&str>) -> Vec<Target> {
+    let mut merged: BTreeMap<(String, u32), Target> = BTreeMap::new();
+
+    for finding in findings {
+        let uri = normalize_path(&finding.uri, source_root);
+        let target = merged.entry((uri.clone(), finding.line)).or_insert_with(|| Target {
+            uri,
+            line: finding.line,
+            tools: BTreeSet::new(),
+            consensus: 0,
+            cmpids: vec![],
+            findings: vec![],
+        });
+        target.tools.insert(finding.tool.clone());
+        target.consensus = target.tools.len();
+        target.findings.push(finding);
+    }
+
+    let mut targets: Vec<Target> = merged.into_values().collect();
+    targets.sort_by_key(|t| std::cmp::Reverse(t.consensus));
+    targets
+}
+
+/// Normalizes the paths of the waypoints, collapses the consecutive
+/// waypoints on the same line and drops the duplicate flows
+pub fn merge_flows(flows: Vec<CodeFlow>, source_root: Option<&str>) -> Vec<CodeFlow> {
+    let mut seen = BTreeSet::new();
+    let mut merged = vec![];
+
+    for mut flow in flows {
+        for waypoint in flow.waypoints.iter_mut() {
+            waypoint.uri = normalize_path(&waypoint.uri, source_root);
+        }
+        flow.waypoints.dedup_by(|a, b| a.uri == b.uri && a.line == b.line);
+
+        let key: Vec<(String, u32)> = flow.waypoints.iter().map(|w| (w.uri.clone(), w.line)).collect();
+        if seen.insert(key) {
+            merged.push(flow);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::test_finding;
+
+    #[test]
+    fn merge_normalize_path() {
+        assert_eq!(normalize_path("file:///src/proj/./lib/../a.c", None), "/src/proj/a.c");
+        assert_eq!(normalize_path("/src/proj/a.c", Some("/src/proj/")), "a.c");
+        assert_eq!(normalize_path("./a.c", Some("/src/proj")), "a.c");
+        assert_eq!(normalize_path("../a.c", None), "../a.c");
+    }
+
+    #[test]
+    fn merge_consensus() {
+        let findings = vec![
+            test_finding("/src/proj/a.c", 3, "infer"),
+            test_finding("a.c", 3, "cppcheck"),
+            test_finding("a.c", 3, "cppcheck"),
+            test_finding("a.c", 4, "infer"),
+        ];
+        let targets = merge(findings, Some("/src/proj"));
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].line, 3);
+        assert_eq!(targets[0].consensus, 2);
+        assert_eq!(targets[0].findings.len(), 3);
+        assert_eq!(targets[1].consensus, 1);
+        assert_eq!(targets[0].weight(), 4);
+        assert_eq!(targets[0].label(), "a.c:3 cppcheck,infer");
+    }
+
+    #[test]
+    fn merge_flows_dedup() {
+        let flows = vec![
+            CodeFlow { waypoints: vec![test_finding("/src/proj/a.c", 3, "codeql"),
+                                       test_finding("a.c", 3, "codeql"),
+                                       test_finding("a.c", 8, "codeql")] },
+            CodeFlow { waypoints: vec![test_finding("a.c", 3, "codeql"), test_finding("a.c", 8, "codeql")] },
+        ];
+        let flows = merge_flows(flows, Some("/src/proj"));
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].waypoints.len(), 2);
+    }
+}
@@ -0,0 +1,434 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::finding::{CodeFlow, Finding};
+
+/// Describes the structure of a SARIF report
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Report {
+    /// Required - SARIF reports must have a version - 2.1.0
+    pub version: String,
+    /// A SARIF log file contains an array of one or more runs
+    #[serde(default)]
+    pub runs: Vec<Run>,
+}
+
+/// Describes the structure of the run, a single invocation of the tool
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct Run {
+    /// Required - The tool which carried out the run
+    pub tool: Tool,
+    /// Optional - The results of the run, absent if the tool failed
+    #[serde(default)]
+    pub results: Vec<Results>,
+    /// Optional - Artifacts referenced by the results, by index
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    /// Optional - Base uris which the uriBaseId of a location refers to
+    #[serde(default)]
+    pub originalUriBaseIds: HashMap<String, ArtifactLocation>,
+}
+
+/// Describes the results of the run
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct Results {
+    /// Optional - Identifier of the rule which was evaluated
+    pub ruleId: Option<String>,
+    /// Optional - Index of the rule in tool.driver.rules
+    pub ruleIndex: Option<usize>,
+    /// Optional - Reference to the rule which was evaluated
+    pub rule: Option<RuleReference>,
+    /// Optional - Severity of the result, defaults to warning
+    #[serde(default = "default_level")]
+    pub level: String,
+    /// Optional - Nature of the result, defaults to fail
+    #[serde(default = "default_kind")]
+    pub kind: String,
+    /// Required - Message string reporting the result
+    pub message: Message,
+    /// Optional - List of location objects
+    #[serde(default)]
+    pub locations: Vec<Location>,
+    /// Optional - Locations relevant to understand the result
+    #[serde(default)]
+    pub relatedLocations: Vec<Location>,
+    /// Optional - Code Flow
+    #[serde(default)]
+    pub codeFlows: Vec<Flows>,
+}
+
+fn default_level() -> String {
+    "warning".to_string()
+}
+
+fn default_kind() -> String {
+    "fail".to_string()
+}
+
+/// Describes a reference to a rule of the tool
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RuleReference {
+    pub id: Option<String>,
+    pub index: Option<usize>,
+}
+
+/// Describes the location property
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct Location {
+    /// Optional - Physical location of the artifact
+    pub physicalLocation: Option<PhysicalLocation>,
+    /// Optional - Functions, classes, ... containing the location
+    #[serde(default)]
+    pub logicalLocations: Vec<LogicalLocation>,
+}
+
+/// Describes the logical location property
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct LogicalLocation {
+    pub name: Option<String>,
+    pub fullyQualifiedName: Option<String>,
+}
+
+/// Describes the code flow property
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct Flows {
+    #[serde(default)]
+    pub threadFlows: Vec<ThreadFlows>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct ThreadFlows {
+    #[serde(default)]
+    pub locations: Vec<ThreadLocations>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct ThreadLocations {
+    pub location: Option<Location>,
+}
+
+/// Describes the physical location of the error
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct PhysicalLocation {
+    pub artifactLocation: Option<ArtifactLocation>,
+    pub region: Option<Region>,
+}
+
+/// Describes the location of the physical artifact
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[allow(non_snake_case)]
+pub struct ArtifactLocation {
+    /// Optional - Absolute uri, or relative to uriBaseId
+    pub uri: Option<String>,
+    /// Optional - Key of run.originalUriBaseIds
+    pub uriBaseId: Option<String>,
+    /// Optional - Index of the artifact in run.artifacts
+    pub index: Option<usize>,
+}
+
+/// Describes an artifact of the run
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Artifact {
+    pub location: Option<ArtifactLocation>,
+}
+
+/// Describes the region in which the error is found
+#[derive(Deserialize, Serialize, Debug)]
+#[allow(non_snake_case)]
+pub struct Region {
+    pub startLine: Option<u32>,
+    pub startColumn: Option<u32>,
+    pub endLine: Option<u32>,
+}
+
+/// Describes the message property
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Message {
+    /// Optional - Text describing the message property
+    pub text: Option<String>,
+    /// Optional - Identifier of the message in the rule message strings
+    pub id: Option<String>,
+}
+
+/// Describes the tool which carried out the run
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Tool {
+    /// Required - Sub-property driver of the tool
+    pub driver: Driver,
+}
+
+/// Describes the sub-property
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Driver {
+    /// Required - Name of the tool which produced the analysis
+    pub name: String,
+    /// Optional - Rules evaluated by the tool
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// Describes a rule evaluated by the tool
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Rule {
+    pub id: String,
+}
+
+/// Reads sarif json format from file
+pub fn read_from_file(path: &Path) -> Result<Report, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let report: Report = serde_json::from_reader(reader)?;
+    if report.version != "2.1.0" {
+        return Err(format!("unsupported SARIF version {}", report.version).into());
+    }
+
+    Ok(report)
+}
+
+/// Reads the report as raw JSON, keeping the properties the model drops
+pub fn read_raw(path: &Path) -> Result<serde_json::Value, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+
+    Ok(serde_json::from_reader(reader)?)
+}
+
+impl Run {
+    /// Resolves an artifact location to an uri, following artifact indexes
+    /// and uri base ids
+    fn resolve_uri(&self, loc: &ArtifactLocation) -> Result<String, String> {
+        self.resolve_uri_depth(loc, 0)
+    }
+
+    fn resolve_uri_depth(&self, loc: &ArtifactLocation, depth: usize) -> Result<String, String> {
+        if depth > 16 {
+            return Err("uriBaseId cycle".to_string());
+        }
+
+        let (uri, base_id) = match (&loc.uri, loc.index) {
+            (Some(uri), _) => (uri.clone(), loc.uriBaseId.clone()),
+            (None, Some(index)) => {
+                let artifact = self.artifacts.get(index)
+                    .and_then(|a| a.location.as_ref())
+                    .ok_or(format!("artifact index {} out of range", index))?;
+                return self.resolve_uri_depth(artifact, depth + 1);
+            },
+            (None, None) => return Err("artifact location without uri".to_string()),
+        };
+
+        let uri = match base_id {
+            Some(id) => {
+                let base = self.originalUriBaseIds.get(&id)
+                    .ok_or(format!("undefined uriBaseId {}", id))?;
+                let mut base_uri = self.resolve_uri_depth(base, depth + 1)?;
+                if !base_uri.is_empty() && !base_uri.ends_with('/') {
+                    base_uri.push('/');
+                }
+                base_uri + &uri
+            },
+            None => uri,
+        };
+
+        Ok(uri.trim_start_matches("file://").to_string())
+    }
+
+    /// Resolves the rule identifier of a result, which may be given directly
+    /// or as an index in the rules of the tool
+    fn resolve_rule_id(&self, res: &Results) -> Option<String> {
+        let rule = res.rule.as_ref();
+        if let Some(id) = res.ruleId.clone().or_else(|| rule.and_then(|r| r.id.clone())) {
+            return Some(id);
+        }
+        let index = res.ruleIndex.or_else(|| rule.and_then(|r| r.index))?;
+        self.tool.driver.rules.get(index).map(|r| r.id.clone())
+    }
+
+    /// Collects the locations of the i-th result, the locations of its code
+    /// flows instead of its own with `code_flow`
+    pub fn result_findings(&self, i: usize, code_flow: bool, related: bool,
+                           diagnostics: &mut Vec<String>) -> Vec<Finding> {
+        let res = &self.results[i];
+        let mut locations = vec![];
+        if !code_flow {
+            locations.extend(&res.locations);
+        }
+        else {
+            for flow in &res.codeFlows {
+                for t_flow in &flow.threadFlows {
+                    for loc in &t_flow.locations {
+                        locations.extend(&loc.location);
+                    }
+                }
+            }
+        }
+        if related {
+            locations.extend(&res.relatedLocations);
+        }
+
+        locations.into_iter()
+            .filter_map(|loc| self.finding_at(res, i, loc, diagnostics))
+            .collect()
+    }
+
+    /// Builds the finding of a location of a result, locations which cannot
+    /// be resolved to a line are reported in diagnostics
+    fn finding_at(&self, res: &Results, i: usize, loc: &Location,
+                  diagnostics: &mut Vec<String>) -> Option<Finding> {
+        let tool = &self.tool.driver.name;
+        let function = loc.logicalLocations.iter()
+            .find_map(|l| l.fullyQualifiedName.clone().or_else(|| l.name.clone()));
+        let physical = match &loc.physicalLocation {
+            Some(physical) => physical,
+            None => {
+                diagnostics.push(format!("{} result {}: no physical location", tool, i));
+                return None;
+            },
+        };
+        let uri = match physical.artifactLocation.as_ref().map(|a| self.resolve_uri(a)) {
+            Some(Ok(uri)) => uri,
+            Some(Err(why)) => {
+                diagnostics.push(format!("{} result {}: {}", tool, i, why));
+                return None;
+            },
+            None => {
+                diagnostics.push(format!("{} result {}: no artifact location", tool, i));
+                return None;
+            },
+        };
+        let region = physical.region.as_ref();
+        let line = match region.and_then(|r| r.startLine) {
+            Some(line) => line,
+            None => {
+                diagnostics.push(format!("{} result {}: no start line in {}", tool, i, uri));
+                return None;
+            },
+        };
+
+        Some(Finding {
+            uri,
+            line,
+            column: region.and_then(|r| r.startColumn),
+            end_line: region.and_then(|r| r.endLine),
+            function,
+            tool: tool.clone(),
+            rule_id: self.resolve_rule_id(res),
+            level: res.level.clone(),
+            kind: res.kind.clone(),
+            message: res.message.text.clone()
+                .or_else(|| res.message.id.clone())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Collects the locations of the results along with the warning which produced them,
+/// locations which cannot be resolved to a line are reported in diagnostics
+pub fn collect_findings(report: Report, code_flow: bool, related: bool,
+                        diagnostics: &mut Vec<String>) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    for r in &report.runs {
+        for i in 0..r.results.len() {
+            findings.extend(r.result_findings(i, code_flow, related, diagnostics));
+        }
+    }
+    findings
+}
+
+/// Collects every thread flow of the results as an ordered sequence of
+/// waypoints, from the source to the sink of the result
+pub fn collect_flows(report: &Report, diagnostics: &mut Vec<String>) -> Vec<CodeFlow> {
+    let mut flows = vec![];
+
+    for r in &report.runs {
+        for (i, res) in r.results.iter().enumerate() {
+            for t_flow in res.codeFlows.iter().flat_map(|f| f.threadFlows.iter()) {
+                let waypoints: Vec<Finding> = t_flow.locations.iter()
+                    .filter_map(|l| l.location.as_ref())
+                    .filter_map(|loc| r.finding_at(res, i, loc, diagnostics))
+                    .collect();
+                if !waypoints.is_empty() {
+                    flows.push(CodeFlow { waypoints });
+                }
+            }
+        }
+    }
+    flows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Report {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn sarif_optional_fields() {
+        let report = parse(r#"{"version": "2.1.0", "runs": [{"tool": {"driver": {"name": "cppcheck"}},
+            "results": [{"message": {"text": "leak"}, "locations": [{"physicalLocation":
+            {"artifactLocation": {"uri": "a.c"}, "region": {"startLine": 3, "startColumn": 7}}}]}]}]}"#);
+        let mut diagnostics = vec![];
+        let findings = collect_findings(report, false, false, &mut diagnostics);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 3);
+        assert_eq!(findings[0].column, Some(7));
+        assert_eq!(findings[0].level, "warning");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn sarif_uri_resolution() {
+        let report = parse(r#"{"version": "2.1.0", "runs": [{"tool": {"driver": {"name": "codeql",
+            "rules": [{"id": "cpp/overflow"}]}},
+            "originalUriBaseIds": {"SRCROOT": {"uri": "file:///src/"}},
+            "artifacts": [{"location": {"uri": "lib/b.c", "uriBaseId": "SRCROOT"}}],
+            "results": [{"ruleIndex": 0, "message": {"text": "overflow"}, "locations": [{"physicalLocation":
+            {"artifactLocation": {"index": 0}, "region": {"startLine": 10}}}]}]}]}"#);
+        let mut diagnostics = vec![];
+        let findings = collect_findings(report, false, false, &mut diagnostics);
+        assert_eq!(findings[0].uri, "/src/lib/b.c");
+        assert_eq!(findings[0].rule_id, Some("cpp/overflow".to_string()));
+    }
+
+    #[test]
+    fn sarif_logical_locations_only() {
+        let report = parse(r#"{"version": "2.1.0", "runs": [{"tool": {"driver": {"name": "infer"}},
+            "results": [{"message": {"text": "npe"}, "locations": [{"logicalLocations":
+            [{"name": "main"}]}]}]}]}"#);
+        let mut diagnostics = vec![];
+        let findings = collect_findings(report, false, false, &mut diagnostics);
+        assert!(findings.is_empty());
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn sarif_code_flows() {
+        let report = parse(r#"{"version": "2.1.0", "runs": [{"tool": {"driver": {"name": "codeql"}},
+            "results": [{"ruleId": "cpp/use-after-free", "message": {"text": "uaf"},
+            "codeFlows": [{"threadFlows": [{"locations": [
+                {"location": {"physicalLocation": {"artifactLocation": {"uri": "a.c"}, "region": {"startLine": 5}}}},
+                {"location": {"message": {"text": "free"}}},
+                {"location": {"physicalLocation": {"artifactLocation": {"uri": "a.c"}, "region": {"startLine": 9}}}},
+                {"location": {"physicalLocation": {"artifactLocation": {"uri": "b.c"}, "region": {"startLine": 2}}}}
+            ]}]}]}]}]}"#);
+        let mut diagnostics = vec![];
+        let flows = collect_flows(&report, &mut diagnostics);
+        assert_eq!(flows.len(), 1);
+        let lines: Vec<u32> = flows[0].waypoints.iter().map(|w| w.line).collect();
+        assert_eq!(lines, vec![5, 9, 2]);
+        assert_eq!(flows[0].waypoints[2].uri, "b.c");
+        assert_eq!(diagnostics.len(), 1);
+    }
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::finding::CodeFlow;
+
+pub type CmpId = u32;
+pub type BbId = u32;
+pub type Edge = (BbId, BbId);
+
+/// Describes a row of the line mapping emitted by the id-assigner pass
+/// (`-idassign-emit-lines`)
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)]
+struct LineRow {
+    /// Source file name as found in the debug info
+    file: String,
+    /// Source line
+    line: u32,
+    /// Angora cmpid of the conditional guarding the line
+    cmpId: CmpId,
+    /// Basic block containing the line
+    bbId: BbId,
+}
+
+/// Describes a row of the static CFG emitted by the id-assigner pass
+/// (`-idassign-emit-cfg`)
+#[derive(Deserialize, Debug)]
+struct EdgeRow {
+    src: BbId,
+    dst: BbId,
+}
+
+/// Describes a row of the call graph emitted by the id-assigner pass
+/// (`-idassign-emit-callgraph`)
+#[derive(Deserialize, Debug)]
+#[allow(non_snake_case)]
+struct CallRow {
+    /// Basic block
+    bbId: BbId,
+    /// Function containing the basic block
+    function: String,
+    /// Function called by the basic block, none for the rows only giving
+    /// the function of the basic block
+    callee: Option<String>,
+}
+
+/// Describes a function of the static call graph (`FunctionInfo` in
+/// `fuzzer/src/dyncfg/fparse.rs`), used by the aflgo distance metric
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub bbs: BTreeSet<BbId>,
+    /// Functions called by each basic block of the function
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub calls: BTreeMap<BbId, BTreeSet<String>>,
+}
+
+/// Source line to cmpid/bbid mapping
+#[derive(Debug, Default)]
+pub struct LineMap {
+    lines: HashMap<(String, u32), BTreeSet<CmpId>>,
+    id_mapping: BTreeMap<BbId, BTreeSet<CmpId>>,
+}
+
+/// Describes the targets file read by the fuzzer (`CfgFile` in
+/// `fuzzer/src/dyncfg/fparse.rs`)
+#[derive(Serialize, Debug, Default)]
+pub struct TargetsFile {
+    pub targets: BTreeSet<CmpId>,
+    pub edges: BTreeSet<Edge>,
+    pub id_mapping: BTreeMap<BbId, BTreeSet<CmpId>>,
+    /// Staged targets, the cmpids of the waypoints of every code flow
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub flows: Vec<Vec<BTreeSet<CmpId>>>,
+    /// Weight and label of the targets, targets without one weigh 1
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub target_info: BTreeMap<CmpId, TargetInfo>,
+    /// Static call graph, for the function level distance
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<Function>,
+}
+
+/// Describes the weight and label of a target (`TargetInfo` in
+/// `fuzzer/src/dyncfg/fparse.rs`), the fuzzer pursues the heaviest targets
+/// first
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TargetInfo {
+    pub weight: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Strips the directories from a path, the debug info only keeps the
+/// file name so targets are matched the same way the id-assigner does it
+fn file_name(uri: &str) -> &str {
+    match uri.rfind(['/', '\\']) {
+        Some(pos) => &uri[pos + 1..],
+        None => uri,
+    }
+}
+
+impl LineMap {
+    /// Reads the `file,line,cmpId,bbId` CSV produced by the id-assigner
+    pub fn from_file(path: &Path) -> Result<LineMap, Box<dyn Error>> {
+        let mut reader = csv::Reader::from_path(path)?;
+        let mut map = LineMap::default();
+
+        for row in reader.deserialize() {
+            let row: LineRow = row?;
+            map.lines
+                .entry((file_name(&row.file).to_string(), row.line))
+                .or_insert_with(BTreeSet::new)
+                .insert(row.cmpId);
+            map.id_mapping
+                .entry(row.bbId)
+                .or_insert_with(BTreeSet::new)
+                .insert(row.cmpId);
+        }
+
+        Ok(map)
+    }
+
+    /// Returns the cmpids covering the given source location
+    pub fn lookup(&self, uri: &str, line: u32) -> Option<&BTreeSet<CmpId>> {
+        self.lines.get(&(file_name(uri).to_string(), line))
+    }
+}
+
+/// Reads the `src,dst` CFG CSV produced by the id-assigner
+pub fn read_edges(path: &Path) -> Result<BTreeSet<Edge>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut edges = BTreeSet::new();
+
+    for row in reader.deserialize() {
+        let row: EdgeRow = row?;
+        edges.insert((row.src, row.dst));
+    }
+
+    Ok(edges)
+}
+
+/// Reads the `bbId,function,callee` call graph CSV produced by the
+/// id-assigner, the functions are sorted by name
+pub fn read_call_graph(path: &Path) -> Result<Vec<Function>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut functions: BTreeMap<String, Function> = BTreeMap::new();
+
+    for row in reader.deserialize() {
+        let row: CallRow = row?;
+        let function = functions.entry(row.function.clone()).or_insert_with_key(|name| Function {
+            name: name.clone(),
+            ..Default::default()
+        });
+        function.bbs.insert(row.bbId);
+        if let Some(callee) = row.callee {
+            function.calls.entry(row.bbId).or_default().insert(callee);
+        }
+    }
+
+    Ok(functions.into_values().collect())
+}
+
+impl TargetsFile {
+    pub fn new(map: &LineMap, edges: BTreeSet<Edge>) -> TargetsFile {
+        TargetsFile {
+            targets: BTreeSet::new(),
+            edges,
+            id_mapping: map.id_mapping.clone(),
+            flows: vec![],
+            target_info: BTreeMap::new(),
+            functions: vec![],
+        }
+    }
+
+    /// Adds the cmpids of a source location as targets, returns the cmpids
+    /// or an empty list if the location is not covered by the mapping
+    pub fn add_target(&mut self, map: &LineMap, uri: &str, line: u32) -> Vec<CmpId> {
+        match map.lookup(uri, line) {
+            Some(cmps) => {
+                self.targets.extend(cmps);
+                cmps.iter().cloned().collect()
+            },
+            None => vec![],
+        }
+    }
+
+    /// Sets the weight and label of cmpids, a cmpid shared by several
+    /// locations keeps the heaviest one
+    pub fn set_info(&mut self, cmpids: &[CmpId], weight: u32, label: &str) {
+        for &cmp in cmpids {
+            let info = self.target_info.entry(cmp).or_insert(TargetInfo { weight: 0, label: None });
+            if weight > info.weight {
+                *info = TargetInfo { weight, label: Some(label.to_string()) };
+            }
+        }
+    }
+
+    /// Adds the waypoints of a code flow as staged targets, waypoints not
+    /// covered by the mapping are skipped, returns the number of stages
+    pub fn add_flow(&mut self, map: &LineMap, flow: &CodeFlow) -> usize {
+        let mut stages: Vec<BTreeSet<CmpId>> = flow.waypoints.iter()
+            .filter_map(|w| map.lookup(&w.uri, w.line).cloned())
+            .collect();
+        stages.dedup();
+
+        let len = stages.len();
+        if len > 0 {
+            self.flows.push(stages);
+        }
+        len
+    }
+
+    /// Writes the targets file in JSON format
+    pub fn write_to_file(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_call_graph() {
+        let path = std::env::temp_dir().join(format!("parser_callgraph_{}.csv", std::process::id()));
+        std::fs::write(&path, "bbId,function,callee\n3,main,\n3,main,parse\n4,main,\n7,parse,\n7,parse,\"a,b\"\n").unwrap();
+        let functions = read_call_graph(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0].name, "main");
+        assert_eq!(functions[0].bbs, vec![3, 4].into_iter().collect());
+        assert_eq!(functions[0].calls[&3], vec!["parse".to_string()].into_iter().collect());
+        assert_eq!(functions[1].calls[&7], vec!["a,b".to_string()].into_iter().collect());
+    }
+}
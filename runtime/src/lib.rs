@@ -2,6 +2,8 @@ pub mod ffds;
 pub mod heapmap;
 pub mod len_label;
 pub mod logger;
+pub mod persistent;
+pub mod shm_input;
 mod tag_set;
 pub mod tag_set_wrap;
 pub mod track;
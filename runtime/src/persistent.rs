@@ -0,0 +1,10 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+// The track program runs a single input per process, see
+// runtime_fast/src/persistent.rs for the fast program
+#[no_mangle]
+pub extern "C" fn __angora_persistent_loop(_max_cnt: u32) -> i32 {
+    !STARTED.swap(true, Ordering::SeqCst) as i32
+}
@@ -1,4 +1,4 @@
-use super::{shm_conds, forkcli, shm_branches};
+use super::{shm_conds, forkcli, shm_branches, shm_input};
 use std::ops::DerefMut;
 
 use std::sync::Once;
@@ -9,6 +9,7 @@ static START: Once = Once::new();
 fn fast_init() {
     START.call_once(|| {
         shm_branches::map_branch_counting_shm();
+        shm_input::map_input_shm();
         forkcli::start_forkcli();
     });
 }
@@ -4,7 +4,17 @@ use std::env;
 
 use byteorder::{LittleEndian, WriteBytesExt};
 use libc;
-use std::{io::prelude::*, os::unix::net::UnixStream, process, time::Duration};
+use std::{io::prelude::*, os::unix::net::UnixStream, process, sync::atomic::Ordering, time::Duration};
+
+// Exits without leaving a stopped persistent child behind
+fn exit(code: i32, stopped_child: Option<i32>) -> ! {
+    if let Some(pid) = stopped_child {
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+    }
+    process::exit(code);
+}
 
 pub fn start_forkcli() {
     match env::var(defs::FORKSRV_SOCKET_PATH_VAR) {
@@ -27,30 +37,47 @@ pub fn start_forkcli() {
             let mut sig_buf = [0; 4];
             super::shm_conds::reset_shm_conds();
 
+            // Child stopped by __angora_persistent_loop, waiting for the next input
+            let mut stopped_child: Option<i32> = None;
+
             loop {
                 if socket.read(&mut sig_buf).is_err() {
                     eprintln!("exit forkcli");
-                    process::exit(0);
+                    exit(0, stopped_child);
                 }
 
-                let child_pid = unsafe { libc::fork() };
-
-                if child_pid == 0 {
-                    super::shm_conds::reset_shm_conds();
-                    return;
-                }
+                let child_pid = match stopped_child.take() {
+                    Some(pid) => {
+                        unsafe {
+                            libc::kill(pid, libc::SIGCONT);
+                        }
+                        pid
+                    },
+                    None => {
+                        let child_pid = unsafe { libc::fork() };
+                        if child_pid == 0 {
+                            super::persistent::IN_FORKSRV.store(true, Ordering::SeqCst);
+                            super::shm_conds::reset_shm_conds();
+                            return;
+                        }
+                        child_pid
+                    },
+                };
 
                 if socket.write_i32::<LittleEndian>(child_pid).is_err() {
-                    process::exit(1);
+                    exit(1, Some(child_pid));
                 }
 
                 let mut status: libc::c_int = 0;
-                if unsafe { libc::waitpid(child_pid, &mut status as *mut libc::c_int, 0) } < 0 {
+                if unsafe { libc::waitpid(child_pid, &mut status as *mut libc::c_int, libc::WUNTRACED) } < 0 {
                     process::exit(1);
                 }
+                if unsafe { libc::WIFSTOPPED(status) } {
+                    stopped_child = Some(child_pid);
+                }
 
                 if socket.write_i32::<LittleEndian>(status).is_err() {
-                    process::exit(1);
+                    exit(1, stopped_child);
                 }
             }
         },
@@ -59,4 +86,3 @@ pub fn start_forkcli() {
         },
     }
 }
-
@@ -1,9 +1,11 @@
 pub mod fast;
 pub mod forkcli;
+pub mod persistent;
 pub mod shm_conds;
 
 mod context;
 mod shm_branches;
+mod shm_input;
 
 #[macro_use]
 extern crate ctor;
@@ -0,0 +1,45 @@
+// Persistent mode: the harness runs several inputs in the same process,
+//   while (__angora_persistent_loop(1000)) {
+//       // read the input and run it
+//   }
+// The process stops itself after every input, the fork server resumes it
+// for the next one (see forkcli.rs).
+
+use angora_common::defs;
+use std::{
+    env,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+// Set in the child of the fork server
+pub static IN_FORKSRV: AtomicBool = AtomicBool::new(false);
+static NUM_RUNS: AtomicU32 = AtomicU32::new(0);
+
+// Iterations allowed by the fuzzer, 0 if it disabled persistent mode
+fn max_runs_from_env() -> u32 {
+    env::var(defs::PERSISTENT_MAX_VAR)
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(0)
+}
+
+#[no_mangle]
+pub extern "C" fn __angora_persistent_loop(max_cnt: u32) -> i32 {
+    let runs = NUM_RUNS.fetch_add(1, Ordering::SeqCst);
+    if runs == 0 {
+        // The first input is already there
+        return 1;
+    }
+
+    let max_runs = max_cnt.min(max_runs_from_env());
+    if !IN_FORKSRV.load(Ordering::SeqCst) || runs >= max_runs {
+        // Exit, the fork server forks a new process for the next input
+        return 0;
+    }
+
+    unsafe {
+        libc::raise(libc::SIGSTOP);
+    }
+    super::shm_conds::reset_shm_conds();
+    1
+}
@@ -0,0 +1,53 @@
+// Test case delivered through shared memory, corresponding to
+// fuzzer/src/executor/shm_input.rs. The harness reads it with
+//   size_t len;
+//   const uint8_t *data = __angora_input_buf(&len);
+//   if (!data) {
+//       // read the input file as usual
+//   }
+
+use angora_common::{
+    defs::INPUT_SHM_ENV_VAR,
+    shm,
+    shm_input::{InputBuf, INPUT_IN_FILE},
+};
+use std::{env, process, ptr};
+
+static mut __ANGORA_INPUT_PTR: *const InputBuf = ptr::null();
+
+// Mapped before the fork server starts, the children share the mapping
+pub fn map_input_shm() {
+    if let Ok(val) = env::var(INPUT_SHM_ENV_VAR) {
+        let shm_id = val.parse::<i32>().expect("Could not parse i32 value.");
+        let mem = shm::SHM::<InputBuf>::from_id(shm_id);
+        if mem.is_fail() {
+            eprintln!("fail to load input shm");
+            process::exit(1);
+        }
+        unsafe {
+            __ANGORA_INPUT_PTR = mem.get_ptr();
+        }
+        // The segment is owned by the fuzzer
+        std::mem::forget(mem);
+    }
+}
+
+// Returns the test case and sets its length, or returns NULL if it has to be
+// read from the input file
+#[no_mangle]
+pub extern "C" fn __angora_input_buf(len: *mut usize) -> *const u8 {
+    let input = unsafe { __ANGORA_INPUT_PTR };
+    if input.is_null() {
+        return ptr::null();
+    }
+    let input = unsafe { &*input };
+    if input.len == INPUT_IN_FILE {
+        return ptr::null();
+    }
+    if !len.is_null() {
+        unsafe {
+            *len = input.len as usize;
+        }
+    }
+    input.buf.as_ptr()
+}
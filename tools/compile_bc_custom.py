@@ -41,9 +41,11 @@ def build_pipeline(bc_file, target_flags="@@", profiling_input_dir="in", is_cpp=
     run_cmd(f"{DIFF_BIN} -json {name}.fast.ll {name}.custom.ll 2> {name}.diff")
     run_cmd(f"USE_FAST=1 {compiler} {cflags} -fsanitize=address -fsanitize=undefined -o {name}.san.fast {bc_file}")
 
-    #7) Gather cmp.map
+    #7) Gather cmp.map, lines.csv and callgraph.csv
     run_cmd(f"opt -load {ID_ASSIGNER_PATH} -idassign -idassign-emit-cfg \
-            -idassign-cfg-file cfg.dat {name}.fast.ll")
+            -idassign-cfg-file cfg.dat -idassign-emit-lines \
+            -idassign-lines-file lines.csv -idassign-emit-callgraph \
+            -idassign-callgraph-file callgraph.csv {name}.fast.ll")
 
     # Print fuzzing command
     print("You can now run your target application using:")
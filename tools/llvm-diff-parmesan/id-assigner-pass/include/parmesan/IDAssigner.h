@@ -56,11 +56,14 @@ private:
 
   void collectBasicBlockId(llvm::BasicBlock *BB);
   CmpIdType getAngoraCmpIdForBB(llvm::BasicBlock *BB);
+  IdentifierType getBlockId(llvm::BasicBlock *BB);
 
 
   void emitInfoFile(const std::string Path) const;
   void emitCfgFile(const std::string Path) const;
   void emitCmpMapFile(const std::string Path) const;
+  void emitLineMapFile(const std::string Path, llvm::Module *M);
+  void emitCallGraphFile(const std::string Path, llvm::Module *M);
   void addCustomTargetsFromFile(const std::string Path, llvm::Module *M);
   void getDebugLoc(const llvm::Instruction *I, std::string &Filename, unsigned &Line);
 };
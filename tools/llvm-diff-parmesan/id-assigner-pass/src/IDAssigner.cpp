@@ -51,6 +51,26 @@ static cl::opt<std::string> ClCfgFile(
     cl::desc("File that will contain the cfg information in CSV format"),
     cl::init("-"), cl::Hidden);
 
+static cl::opt<bool> ClEmitLines(
+    "idassign-emit-lines",
+    cl::desc("Write the source line to cmpid/bbid mapping to a file."),
+    cl::init(false), cl::Hidden);
+
+static cl::opt<std::string> ClLinesFile(
+    "idassign-lines-file",
+    cl::desc("File that will contain the line mapping in CSV format"),
+    cl::init("lines.csv"), cl::Hidden);
+
+static cl::opt<bool> ClEmitCallGraph(
+    "idassign-emit-callgraph",
+    cl::desc("Write the function and the direct callees of the basic blocks to a file."),
+    cl::init(false), cl::Hidden);
+
+static cl::opt<std::string> ClCallGraphFile(
+    "idassign-callgraph-file",
+    cl::desc("File that will contain the call graph in CSV format"),
+    cl::init("callgraph.csv"), cl::Hidden);
+
 static cl::opt<bool> ClFollowIndDominators(
     "parmesan-follow-dominators",
     cl::desc("Collect all indirect call dominators, rather than just the closest"),
@@ -249,6 +269,12 @@ bool IDAssigner::runOnModule(Module &M) {
     emitCfgFile(ClCfgFile);
     emitCmpMapFile("cmp.map");
   }
+  if (ClEmitLines) {
+    emitLineMapFile(ClLinesFile, &M);
+  }
+  if (ClEmitCallGraph) {
+    emitCallGraphFile(ClCallGraphFile, &M);
+  }
 
   return false;
 }
@@ -581,5 +607,109 @@ void IDAssigner::emitCmpMapFile(const std::string Path) const {
       }
   }
 }
+
+// Quotes a CSV field if it needs to, doubling the quotes it contains
+static std::string csvField(const std::string &Field) {
+  if (Field.find_first_of(",\"\r\n") == std::string::npos)
+    return Field;
+  std::string Quoted = "\"";
+  for (char C : Field) {
+    if (C == '"')
+      Quoted += '"';
+    Quoted += C;
+  }
+  return Quoted + "\"";
+}
+
+void IDAssigner::emitLineMapFile(const std::string Path, Module *M) {
+  std::error_code EC;
+  raw_fd_ostream InfoFile(Path, EC);
+  if (EC) {
+    errs() << formatv("Could not open info file: {0}\n", Path);
+    return;
+  }
+
+  std::set<std::tuple<std::string, unsigned, CmpIdType, IdentifierType>> Lines;
+  for (auto &F : *M) {
+    for (auto &BB : F) {
+      // Lookups only, the maps must not grow while emitting
+      auto BbIt = IdMap.find(&BB);
+      if (BbIt == IdMap.end() || BbIt->second == 0)
+        continue;
+      auto BbId = BbIt->second;
+      auto CmpIt = IdToAngoraMap.find(BbId);
+      if (CmpIt == IdToAngoraMap.end() || CmpIt->second == 0)
+        continue;
+      auto CmpId = CmpIt->second;
+      for (auto &I : BB) {
+        std::string Filename;
+        unsigned Line = 0;
+        getDebugLoc(&I, Filename, Line);
+        if (Filename.empty() || Line == 0)
+          continue;
+        Lines.insert(std::make_tuple(Filename, Line, CmpId, BbId));
+      }
+    }
+  }
+
+  InfoFile << "file,line,cmpId,bbId\n";
+  for (const auto &E : Lines) {
+    // Note: ParmeSan expects the id as a u32, not i32
+    InfoFile << formatv("{0},{1},", csvField(std::get<0>(E)), std::get<1>(E))
+             << formatv("{0},{1}", (uint32_t)std::get<2>(E), (uint64_t)std::get<3>(E)) << "\n";
+  }
+}
+// Id of a basic block, or of the closest predecessor with one for the blocks
+// split by the Angora instrumentation. Lookups only, like emitLineMapFile.
+IDAssigner::IdentifierType IDAssigner::getBlockId(BasicBlock *BB) {
+  for (idf_iterator<BasicBlock*> I = idf_begin(BB), E = idf_end(BB); I != E; ++I) {
+    auto It = IdMap.find(*I);
+    if (It != IdMap.end() && It->second != 0)
+      return It->second;
+  }
+  return 0;
+}
+
+// One row per basic block and direct callee, with an empty callee for the
+// blocks calling no function. The fuzzer builds the function level distance
+// of the aflgo metric on it.
+void IDAssigner::emitCallGraphFile(const std::string Path, Module *M) {
+  std::error_code EC;
+  raw_fd_ostream InfoFile(Path, EC);
+  if (EC) {
+    errs() << formatv("Could not open info file: {0}\n", Path);
+    return;
+  }
+
+  std::set<std::tuple<IdentifierType, std::string, std::string>> Rows;
+  for (auto &F : *M) {
+    if (F.isDeclaration())
+      continue;
+    std::string FunctionName = F.getName().str();
+    for (auto &BB : F) {
+      auto BbId = getBlockId(&BB);
+      if (BbId == 0)
+        continue;
+      Rows.insert(std::make_tuple(BbId, FunctionName, std::string()));
+      for (auto &I : BB) {
+        auto *CI = dyn_cast<CallInst>(&I);
+        if (!CI)
+          continue;
+        // Calls to functions without a body never reach a target
+        Function *Callee = CI->getCalledFunction();
+        if (!Callee || Callee->isDeclaration() || Callee->isIntrinsic())
+          continue;
+        Rows.insert(std::make_tuple(BbId, FunctionName, Callee->getName().str()));
+      }
+    }
+  }
+
+  InfoFile << "bbId,function,callee\n";
+  for (const auto &E : Rows) {
+    InfoFile << formatv("{0},", (uint64_t)std::get<0>(E)) << csvField(std::get<1>(E))
+             << "," << csvField(std::get<2>(E)) << "\n";
+  }
+}
+
 static RegisterPass<IDAssigner> X{
     "idassign", "IDAssigner: assign unique IDs to LLVM IR values.", true, true};